@@ -28,6 +28,22 @@ complete -c climark -kn "__fish_climark_using_subcommand upload-assessment; and
     -a "(climark list-courses --format=plain --silent)"
 complete -c climark -kn '__fish_climark_using_subcommand upload-assessment; and test (count (commandline -opc)) -eq 3' \
     -a "(climark list-assessments (commandline -opc)[3] --format=plain --silent)"
+complete -c climark -kn "__fish_climark_using_subcommand draft; and __fish_seen_subcommand_from delete list move reorder; and test (count (commandline -opc)) -eq 3" \
+    -a "(climark list-courses --format=plain --silent)"
+complete -c climark -kn '__fish_climark_using_subcommand draft; and __fish_seen_subcommand_from delete list move reorder; and test (count (commandline -opc)) -eq 4' \
+    -a "(climark list-assessments (commandline -opc)[4] --format=plain --silent)"
+complete -c climark -kn '__fish_climark_using_subcommand draft; and __fish_seen_subcommand_from move; and test (count (commandline -opc)) -eq 5' \
+    -a "(climark draft list (commandline -opc)[5] --format=plain --silent)"
+complete -c climark -n '__fish_climark_using_subcommand draft; and __fish_seen_subcommand_from delete reorder' -l pages -x \
+    -a "(climark draft list (commandline -opc)[-2] --format=plain --silent)"
+complete -c climark -kn "__fish_climark_using_subcommand download-submission; and test (count (commandline -opc)) -eq 2" \
+    -a "(climark list-courses --format=plain --silent)"
+complete -c climark -kn '__fish_climark_using_subcommand download-submission; and test (count (commandline -opc)) -eq 3' \
+    -a "(climark list-assessments (commandline -opc)[3] --format=plain --silent)"
+complete -c climark -kn "__fish_climark_using_subcommand feedback; and test (count (commandline -opc)) -eq 2" \
+    -a "(climark list-courses --format=plain --silent)"
+complete -c climark -kn '__fish_climark_using_subcommand feedback; and test (count (commandline -opc)) -eq 3' \
+    -a "(climark list-assessments (commandline -opc)[3] --format=plain --silent)"
 "#
     )?;
 
@@ -0,0 +1,94 @@
+//! An in-process mock of the Crowdmark endpoints used by the `crowdmark`
+//! crate, for offline integration tests.
+//!
+//! The server keeps its [`State`] in memory, so a full sign-in, draft, upload
+//! and submit flow can be exercised and inspected without network access.
+
+mod routes;
+mod state;
+
+pub use state::{
+    Assignment, Course, ExamKind, Fault, Page, Question, RecordedRequest, S3Object, State, User,
+};
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+type Shared = Arc<Mutex<State>>;
+
+/// A running mock Crowdmark server bound to a random local port.
+///
+/// The server stops when this value is dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    base_url: String,
+    state: Shared,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Starts a server with an empty [`State`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    #[inline]
+    pub async fn start() -> Self {
+        Self::with_state(State::default()).await
+    }
+
+    /// Starts a server serving `state`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    #[inline]
+    pub async fn with_state(mut state: State) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind mock server");
+        let addr = listener
+            .local_addr()
+            .expect("Failed to read mock server address");
+        let base_url = format!("http://{addr}/");
+        base_url.clone_into(&mut state.base_url);
+
+        let state = Arc::new(Mutex::new(state));
+        let app = routes::router(Arc::<Mutex<State>>::clone(&state));
+        let task = tokio::spawn(async move {
+            axum::serve(listener, app)
+                .await
+                .expect("Mock server failed");
+        });
+
+        Self {
+            base_url,
+            state,
+            task,
+        }
+    }
+
+    /// Returns the base URL to pass to `ClientBuilder::base_url`.
+    #[inline]
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Locks and returns the server state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a request handler panicked while holding the lock.
+    #[inline]
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("mock state mutex poisoned")
+    }
+}
+
+impl Drop for MockServer {
+    #[inline]
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
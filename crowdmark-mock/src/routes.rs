@@ -0,0 +1,698 @@
+use crate::Shared;
+use crate::state::{Assignment, Course, ExamKind, Page, Question, S3Object, State};
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Form, Multipart, Path, Query, Request, State as Extract};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, patch, post};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+type Reply = Result<Response, Rejection>;
+
+/// Why a request was refused, rendered the way Crowdmark renders it.
+enum Rejection {
+    SignIn,
+    Error(StatusCode, String),
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::SignIn => (StatusCode::FOUND, [(header::LOCATION, "/sign-in")]).into_response(),
+            Self::Error(status, detail) => (
+                status,
+                axum::Json(
+                    json!({ "errors": [{ "status": status.as_u16().to_string(), "detail": detail }] }),
+                ),
+            )
+                .into_response(),
+        }
+    }
+}
+
+pub(crate) fn router(state: Shared) -> Router {
+    Router::new()
+        .route("/sign-in", get(sign_in_page).post(sign_in))
+        .route("/student", get(student_page))
+        .route("/api/v1/s3_policies", post(s3_policy))
+        .route("/api/v2/student/courses", get(list_courses))
+        .route("/api/v2/student/assignments", get(list_assignments))
+        .route(
+            "/api/v2/student/assignments/{id}",
+            get(get_assignment).put(submit_assignment),
+        )
+        .route(
+            "/api/v2/student/assignments/{id}/start-drafting",
+            post(start_drafting),
+        )
+        .route("/api/v2/student/assignment-pages", post(create_page))
+        .route("/api/v2/student/assignment-pages/{id}", patch(update_page))
+        .route(
+            "/api/v2/student/assignment-questions/{id}",
+            patch(update_question),
+        )
+        .route("/s3", post(s3_upload))
+        .route("/s3/{*key}", get(s3_download))
+        .layer(middleware::from_fn_with_state(state.clone(), record))
+        .with_state(state)
+}
+
+async fn record(Extract(shared): Extract<Shared>, request: Request, next: Next) -> Response {
+    let session = session_cookie(request.headers()).map(str::to_owned);
+    let fault = {
+        let mut state = lock(&shared);
+        state.requests.push(crate::RecordedRequest {
+            method: request.method().to_string(),
+            path: request.uri().path().to_owned(),
+        });
+        state
+            .faults
+            .iter()
+            .position(|fault| {
+                fault.method == request.method().as_str()
+                    && request.uri().path().starts_with(&fault.path)
+            })
+            .map(|index| state.faults.remove(index))
+    };
+    let Some(fault) = fault else {
+        let mut response = next.run(request).await;
+        rotate_session(&shared, session.as_deref(), &mut response);
+        return response;
+    };
+
+    if fault.after_handling {
+        next.run(request).await;
+    }
+    let status = StatusCode::from_u16(fault.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut response = error(status, "Injected fault").into_response();
+    if let Some(seconds) = fault.retry_after {
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, seconds.into());
+    }
+    response
+}
+
+/// Swaps `session` for a new one if sessions rotate, telling the client
+/// through `response`.
+fn rotate_session(shared: &Shared, session: Option<&str>, response: &mut Response) {
+    let mut state = lock(shared);
+    let Some(session) = session else {
+        return;
+    };
+    if !state.rotate_sessions || !state.sessions.remove(session) {
+        return;
+    }
+    let token = state.add_session();
+    if let Ok(cookie) = format!("cm_session_id={token}; Path=/; HttpOnly").parse() {
+        response.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+}
+
+fn lock(shared: &Shared) -> std::sync::MutexGuard<'_, State> {
+    shared.lock().expect("mock state mutex poisoned")
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix("cm_session_id="))
+}
+
+fn require_session(state: &State, headers: &HeaderMap) -> Result<(), Rejection> {
+    match session_cookie(headers) {
+        Some(token) if state.sessions.contains(token) => Ok(()),
+        _ => Err(Rejection::SignIn),
+    }
+}
+
+fn require_csrf(state: &State, headers: &HeaderMap) -> Result<(), Rejection> {
+    let token = headers
+        .get("X-Csrf-Token")
+        .and_then(|value| value.to_str().ok());
+    if token == Some(state.csrf_token.as_str()) {
+        Ok(())
+    } else {
+        Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Invalid authenticity token",
+        ))
+    }
+}
+
+fn error(status: StatusCode, detail: &str) -> Rejection {
+    Rejection::Error(status, detail.to_owned())
+}
+
+fn not_found(kind: &str, id: &str) -> Rejection {
+    error(StatusCode::NOT_FOUND, &format!("No {kind} with id {id}"))
+}
+
+fn parse_body(body: &Bytes) -> Result<Value, Rejection> {
+    serde_json::from_slice(body).map_err(|err| error(StatusCode::BAD_REQUEST, &err.to_string()))
+}
+
+fn jsonapi(status: StatusCode, document: Value) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/vnd.api+json")],
+        document.to_string(),
+    )
+        .into_response()
+}
+
+fn course_json(course: &Course, assignment_count: usize) -> Value {
+    let archivation = course.archived.then(
+        || json!({ "type": "course-archivations", "id": format!("{}-archivation", course.id) }),
+    );
+    json!({
+        "id": course.id,
+        "type": "courses",
+        "attributes": {
+            "name": course.name,
+            "exam-master-count": assignment_count,
+        },
+        "relationships": {
+            "course-archivation": { "data": archivation },
+        },
+    })
+}
+
+fn exam_master_json(assignment: &Assignment) -> Value {
+    let kind = match assignment.kind {
+        ExamKind::AtHome => "ExamMaster::AtHome",
+        ExamKind::Proctored => "ExamMaster::Proctored",
+    };
+    json!({
+        "id": assignment.exam_master_id,
+        "type": "exam-masters",
+        "attributes": { "type": kind, "title": assignment.title },
+    })
+}
+
+fn assignment_json(assignment: &Assignment) -> Value {
+    // Crowdmark sends a bare `0` instead of a string when nothing is graded.
+    let normalized_points = assignment
+        .normalized_points
+        .map_or_else(|| json!(0), |points| json!(points.to_string()));
+    json!({
+        "id": assignment.id,
+        "type": "assignments",
+        "attributes": {
+            "normalized-points": normalized_points,
+            "submitted-at": assignment.submitted_at,
+            "due": assignment.due,
+            "late-due": assignment.late_due,
+            "marks-sent-at": assignment.marks_sent_at,
+        },
+        "relationships": {
+            "exam-master": {
+                "data": { "type": "exam-masters", "id": assignment.exam_master_id },
+            },
+            "assignment-questions": {
+                "data": assignment.questions.iter()
+                    .map(|q| json!({ "type": "assignment-questions", "id": q.id }))
+                    .collect::<Vec<_>>(),
+            },
+            "assignment-pages": {
+                "data": assignment.pages.iter()
+                    .map(|p| json!({ "type": "assignment-pages", "id": p.id }))
+                    .collect::<Vec<_>>(),
+            },
+        },
+    })
+}
+
+fn question_json(assignment: &Assignment, question: &Question) -> Value {
+    // Scores stay hidden until marks are sent back to students.
+    let score = assignment.marks_sent_at.and(question.score);
+    let anchor = question
+        .anchored
+        .then(|| json!({ "type": "exam-pages", "id": format!("{}-exam-page", question.id) }));
+    json!({
+        "id": question.id,
+        "type": "assignment-questions",
+        "attributes": {
+            "sequence": question.sequence,
+            "label": question.label,
+            "points": question.points,
+            "score": score,
+        },
+        "relationships": {
+            "anchored-to-exam-page": { "data": anchor },
+            "assignment": { "data": { "type": "assignments", "id": assignment.id } },
+        },
+    })
+}
+
+fn comment_json(question: &Question, index: usize, body: &str) -> Value {
+    json!({
+        "id": format!("{}-comment-{index}", question.id),
+        "type": "comments",
+        "attributes": { "body": body },
+        "relationships": {
+            "question": { "data": { "type": "assignment-questions", "id": question.id } },
+        },
+    })
+}
+
+fn page_json(base_url: &str, page: &Page) -> Value {
+    let marked_url = page
+        .annotated_key
+        .as_ref()
+        .map(|key| format!("{base_url}s3/{key}"));
+    json!({
+        "id": page.id,
+        "type": "assignment-pages",
+        "attributes": {
+            "number": page.number,
+            "filename": page.filename,
+            "uuid": page.uuid,
+            "url": format!("{base_url}s3/uploads/{}", page.uuid),
+            "marked-url": marked_url,
+        },
+        "relationships": {
+            "question": {
+                "data": page.question_id.as_ref().map(|id| json!({ "type": "assignment-questions", "id": id })),
+            },
+        },
+    })
+}
+
+async fn sign_in_page(Extract(shared): Extract<Shared>) -> Html<String> {
+    let token = lock(&shared).authenticity_token.clone();
+    Html(format!(
+        r#"<form action="/sign-in" method="post"><input type="hidden" name="authenticity_token" value="{token}" /></form>"#
+    ))
+}
+
+async fn sign_in(
+    Extract(shared): Extract<Shared>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let mut state = lock(&shared);
+    let field = |name: &str| form.get(name).map(String::as_str);
+    let valid = field("authenticity_token") == Some(state.authenticity_token.as_str())
+        && state.users.iter().any(|user| {
+            field("user[email]") == Some(user.email.as_str())
+                && field("user[password]") == Some(user.password.as_str())
+        });
+    if !valid {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Html("Invalid email or password"),
+        )
+            .into_response();
+    }
+
+    let token = state.add_session();
+    (
+        [(
+            header::SET_COOKIE,
+            format!("cm_session_id={token}; Path=/; HttpOnly"),
+        )],
+        Html("Signed in"),
+    )
+        .into_response()
+}
+
+async fn student_page(Extract(shared): Extract<Shared>, headers: HeaderMap) -> Reply {
+    let state = lock(&shared);
+    require_session(&state, &headers)?;
+    Ok(Html(format!(
+        r#"<html><head><meta name="csrf-token" content="{}" /></head></html>"#,
+        state.csrf_token
+    ))
+    .into_response())
+}
+
+async fn list_courses(Extract(shared): Extract<Shared>, headers: HeaderMap) -> Reply {
+    let state = lock(&shared);
+    require_session(&state, &headers)?;
+    let data: Vec<_> = state
+        .courses
+        .iter()
+        .map(|course| {
+            let count = state
+                .assignments
+                .iter()
+                .filter(|a| a.course_id == course.id)
+                .count();
+            course_json(course, count)
+        })
+        .collect();
+    let included: Vec<_> = state
+        .courses
+        .iter()
+        .filter(|course| course.archived)
+        .map(|course| {
+            json!({ "type": "course-archivations", "id": format!("{}-archivation", course.id) })
+        })
+        .collect();
+    Ok(jsonapi(
+        StatusCode::OK,
+        json!({ "data": data, "included": included }),
+    ))
+}
+
+async fn list_assignments(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Reply {
+    let state = lock(&shared);
+    require_session(&state, &headers)?;
+    let course_id = query
+        .iter()
+        .find(|(key, _)| key == "filter[course]")
+        .map(|(_, value)| value.as_str());
+    let assignments: Vec<_> = state
+        .assignments
+        .iter()
+        .filter(|a| course_id.is_none_or(|id| a.course_id == id))
+        .collect();
+    Ok(jsonapi(
+        StatusCode::OK,
+        json!({
+            "data": assignments.iter().map(|a| assignment_json(a)).collect::<Vec<_>>(),
+            "included": assignments.iter().map(|a| exam_master_json(a)).collect::<Vec<_>>(),
+        }),
+    ))
+}
+
+async fn get_assignment(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Reply {
+    let state = lock(&shared);
+    require_session(&state, &headers)?;
+    let assignment = state
+        .assignment(&id)
+        .ok_or_else(|| not_found("assignment", &id))?;
+    let included: Vec<_> = std::iter::once(exam_master_json(assignment))
+        .chain(
+            assignment
+                .questions
+                .iter()
+                .map(|q| question_json(assignment, q)),
+        )
+        .chain(
+            assignment
+                .pages
+                .iter()
+                .map(|p| page_json(&state.base_url, p)),
+        )
+        .chain(
+            assignment
+                .questions
+                .iter()
+                .filter(|_| assignment.marks_sent_at.is_some())
+                .flat_map(|q| {
+                    q.comments
+                        .iter()
+                        .enumerate()
+                        .map(move |(index, body)| comment_json(q, index, body))
+                }),
+        )
+        .collect();
+    Ok(jsonapi(
+        StatusCode::OK,
+        json!({ "data": assignment_json(assignment), "included": included }),
+    ))
+}
+
+async fn start_drafting(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Reply {
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+    require_csrf(&state, &headers)?;
+    let assignment = state
+        .assignment_mut(&id)
+        .ok_or_else(|| not_found("assignment", &id))?;
+    assignment.drafting = true;
+    let document = json!({ "data": assignment_json(assignment) });
+    Ok(jsonapi(StatusCode::OK, document))
+}
+
+async fn submit_assignment(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Reply {
+    let body = parse_body(&body)?;
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+    require_csrf(&state, &headers)?;
+
+    let signature = body["signature"].as_str().unwrap_or_default();
+    if !state.upload_signatures.remove(signature) {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Invalid upload signature",
+        ));
+    }
+
+    let assignment = state
+        .assignment_mut(&id)
+        .ok_or_else(|| not_found("assignment", &id))?;
+    assignment.drafting = false;
+    assignment.submitted_at = Some(chrono::Utc::now());
+    assignment.submitted_pages = assignment.pages.clone();
+    let document = json!({ "data": assignment_json(assignment) });
+    Ok(jsonapi(StatusCode::OK, document))
+}
+
+async fn create_page(Extract(shared): Extract<Shared>, headers: HeaderMap, body: Bytes) -> Reply {
+    let body = parse_body(&body)?;
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+    require_csrf(&state, &headers)?;
+
+    let attributes = &body["data"]["attributes"];
+    let relationships = &body["data"]["relationships"];
+    let assignment_id = relationships["assignment"]["data"]["id"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned();
+    let question_id = relationships["question"]["data"]["id"]
+        .as_str()
+        .map(str::to_owned);
+    let uuid = attributes["uuid"].as_str().unwrap_or_default().to_owned();
+    if !state.s3_objects.contains_key(&format!("uploads/{uuid}")) {
+        return Err(error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "No uploaded file for uuid",
+        ));
+    }
+
+    let page = Page {
+        annotated_key: None,
+        filename: attributes["filename"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned(),
+        id: state.next_id("page"),
+        number: attributes["number"].as_i64().unwrap_or_default(),
+        question_id: question_id.clone(),
+        uuid,
+    };
+    let document = json!({ "data": page_json(&state.base_url, &page) });
+    let page_limit = state.page_limit;
+    let assignment = state
+        .assignment_mut(&assignment_id)
+        .ok_or_else(|| not_found("assignment", &assignment_id))?;
+    if let Some(question_id) = &question_id
+        && !assignment.questions.iter().any(|q| q.id == *question_id)
+    {
+        return Err(not_found("assignment-question", question_id));
+    }
+    if page_limit.is_some_and(|limit| assignment.pages.len() >= limit) {
+        return Err(error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Page limit reached",
+        ));
+    }
+    if attributes["is-anchor"] == true
+        && let Some(question) = assignment
+            .questions
+            .iter_mut()
+            .find(|q| question_id.as_ref() == Some(&q.id))
+    {
+        question.anchored = true;
+    }
+    assignment.pages.push(page);
+    Ok(jsonapi(StatusCode::CREATED, document))
+}
+
+async fn update_page(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Reply {
+    let body = parse_body(&body)?;
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+    require_csrf(&state, &headers)?;
+
+    let base_url = state.base_url.clone();
+    let assignment = state
+        .assignments
+        .iter_mut()
+        .find(|a| a.pages.iter().any(|p| p.id == id))
+        .ok_or_else(|| not_found("assignment-page", &id))?;
+    let index = assignment
+        .pages
+        .iter()
+        .position(|p| p.id == id)
+        .ok_or_else(|| not_found("assignment-page", &id))?;
+
+    if body["data"]["attributes"]["state"] == "pending_delete" {
+        let page = assignment.pages.remove(index);
+        return Ok(jsonapi(
+            StatusCode::OK,
+            json!({ "data": page_json(&base_url, &page) }),
+        ));
+    }
+
+    let page = &mut assignment.pages[index];
+    if let Some(number) = body["data"]["attributes"]["number"].as_i64() {
+        page.number = number;
+    }
+    if let Some(question_id) = body["data"]["relationships"]["question"]["data"]["id"]
+        .as_str()
+        .filter(|id| !id.is_empty())
+    {
+        page.question_id = Some(question_id.to_owned());
+        if let Some(question) = assignment
+            .questions
+            .iter_mut()
+            .find(|q| q.id == question_id)
+        {
+            question.anchored = true;
+        }
+    }
+    Ok(jsonapi(
+        StatusCode::OK,
+        json!({ "data": page_json(&base_url, page) }),
+    ))
+}
+
+async fn update_question(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Reply {
+    let body = parse_body(&body)?;
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+    require_csrf(&state, &headers)?;
+
+    let assignment = state
+        .assignments
+        .iter_mut()
+        .find(|a| a.questions.iter().any(|q| q.id == id))
+        .ok_or_else(|| not_found("assignment-question", &id))?;
+    let assignment_snapshot = assignment.clone();
+    let question = assignment
+        .questions
+        .iter_mut()
+        .find(|q| q.id == id)
+        .ok_or_else(|| not_found("assignment-question", &id))?;
+    let anchor = &body["data"]["relationships"]["anchored-to-exam-page"];
+    if anchor.is_object() {
+        question.anchored = !anchor["data"].is_null();
+    }
+    let document = json!({ "data": question_json(&assignment_snapshot, question) });
+    Ok(jsonapi(StatusCode::OK, document))
+}
+
+async fn s3_policy(
+    Extract(shared): Extract<Shared>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Reply {
+    let mut state = lock(&shared);
+    require_session(&state, &headers)?;
+
+    let enrollment = form.get("enrollment_uuid").cloned().unwrap_or_default();
+    if state.assignment(&enrollment).is_none() {
+        return Err(not_found("assignment", &enrollment));
+    }
+    let uuid = match form.get("requested_uuid") {
+        Some(uuid) => uuid.clone(),
+        None => state.next_id("upload"),
+    };
+    let signature = state.next_id("signature");
+    state.upload_signatures.insert(signature.clone());
+
+    Ok(axum::Json(json!({
+        "bucket": format!("{}s3", state.base_url),
+        "key": format!("uploads/{uuid}"),
+        "fields": [["policy", "mock-policy"], ["x-amz-signature", "mock-signature"]],
+        "upload_signature": signature,
+    }))
+    .into_response())
+}
+
+async fn s3_upload(Extract(shared): Extract<Shared>, mut multipart: Multipart) -> Reply {
+    let mut fields = HashMap::new();
+    let mut file = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| error(StatusCode::BAD_REQUEST, &err.to_string()))?
+    {
+        let name = field.name().unwrap_or_default().to_owned();
+        let data = field
+            .bytes()
+            .await
+            .map_err(|err| error(StatusCode::BAD_REQUEST, &err.to_string()))?;
+        if name == "file" {
+            file = Some(data.to_vec());
+        } else {
+            fields.insert(name, String::from_utf8_lossy(&data).into_owned());
+        }
+    }
+
+    let (Some(key), Some(data)) = (fields.remove("key"), file) else {
+        return Err(error(StatusCode::BAD_REQUEST, "Missing key or file"));
+    };
+    if fields.get("policy").map(String::as_str) != Some("mock-policy") {
+        return Err(error(StatusCode::FORBIDDEN, "Invalid policy"));
+    }
+
+    lock(&shared).s3_objects.insert(
+        key,
+        S3Object {
+            content_type: fields.remove("Content-Type").unwrap_or_default(),
+            data,
+        },
+    );
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn s3_download(Extract(shared): Extract<Shared>, Path(key): Path<String>) -> Reply {
+    let state = lock(&shared);
+    let object = state
+        .s3_objects
+        .get(&key)
+        .ok_or_else(|| not_found("object", &key))?;
+    Ok((
+        [(header::CONTENT_TYPE, object.content_type.clone())],
+        object.data.clone(),
+    )
+        .into_response())
+}
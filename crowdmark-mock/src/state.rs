@@ -0,0 +1,282 @@
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Everything the mock server knows about, shared between request handlers.
+///
+/// Fields are public so tests can seed fixtures before a request and inspect
+/// the outcome afterwards.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct State {
+    pub assignments: Vec<Assignment>,
+    pub authenticity_token: String,
+    pub base_url: String,
+    pub courses: Vec<Course>,
+    pub csrf_token: String,
+    /// Failures injected into upcoming requests, used up in order.
+    pub faults: Vec<Fault>,
+    /// Makes page creation fail once an assignment holds this many pages.
+    pub page_limit: Option<usize>,
+    pub requests: Vec<RecordedRequest>,
+    /// Replaces the session of every authenticated request with a new one,
+    /// sent back in `Set-Cookie`, as Crowdmark does when refreshing cookies.
+    pub rotate_sessions: bool,
+    pub s3_objects: HashMap<String, S3Object>,
+    pub sessions: HashSet<String>,
+    pub upload_signatures: HashSet<String>,
+    pub users: Vec<User>,
+    next_id: usize,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Course {
+    pub archived: bool,
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExamKind {
+    #[default]
+    AtHome,
+    Proctored,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Assignment {
+    pub course_id: String,
+    pub drafting: bool,
+    pub due: Option<DateTime<Utc>>,
+    pub exam_master_id: String,
+    pub id: String,
+    pub kind: ExamKind,
+    pub late_due: Option<DateTime<Utc>>,
+    pub marks_sent_at: Option<DateTime<Utc>>,
+    pub normalized_points: Option<f32>,
+    pub pages: Vec<Page>,
+    pub questions: Vec<Question>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    pub submitted_pages: Vec<Page>,
+    pub title: String,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Question {
+    pub anchored: bool,
+    pub comments: Vec<String>,
+    pub id: String,
+    pub label: String,
+    pub points: f32,
+    pub score: Option<f32>,
+    pub sequence: usize,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Page {
+    pub annotated_key: Option<String>,
+    pub filename: String,
+    pub id: String,
+    pub number: i64,
+    /// `None` for pages not anchored to any question.
+    pub question_id: Option<String>,
+    pub uuid: String,
+}
+
+/// A failure returned for the next request matching `method` and `path`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Fault {
+    /// Handle the request before failing, as if its response were lost.
+    pub after_handling: bool,
+    pub method: String,
+    /// Prefix of the request paths this fault applies to.
+    pub path: String,
+    /// Sent as the `Retry-After` header, in seconds.
+    pub retry_after: Option<u64>,
+    pub status: u16,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct S3Object {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct User {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+}
+
+impl Default for State {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            assignments: Vec::new(),
+            authenticity_token: "mock-authenticity-token".to_owned(),
+            base_url: String::new(),
+            courses: Vec::new(),
+            csrf_token: "mock-csrf-token".to_owned(),
+            faults: Vec::new(),
+            page_limit: None,
+            requests: Vec::new(),
+            rotate_sessions: false,
+            s3_objects: HashMap::new(),
+            sessions: HashSet::new(),
+            upload_signatures: HashSet::new(),
+            users: Vec::new(),
+            next_id: 1,
+        }
+    }
+}
+
+impl State {
+    /// Returns a fresh identifier, unique within this server.
+    #[inline]
+    pub fn next_id(&mut self, prefix: &str) -> String {
+        let id = format!("{prefix}-{}", self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Registers a user that can sign in with `email` and `password`.
+    #[inline]
+    pub fn add_user(&mut self, email: &str, password: &str) {
+        self.users.push(User {
+            email: email.to_owned(),
+            password: password.to_owned(),
+        });
+    }
+
+    /// Creates a valid session and returns its `cm_session_id`.
+    #[inline]
+    pub fn add_session(&mut self) -> String {
+        let token = self.next_id("session");
+        self.sessions.insert(token.clone());
+        token
+    }
+
+    /// Makes the next `method` request to a path starting with `path` fail
+    /// with `status`, and returns the fault for further tweaking.
+    #[inline]
+    pub fn add_fault(&mut self, method: &str, path: &str, status: u16) -> &mut Fault {
+        self.faults.push(Fault {
+            after_handling: false,
+            method: method.to_owned(),
+            path: path.to_owned(),
+            retry_after: None,
+            status,
+        });
+        self.faults.last_mut().expect("fault was just pushed")
+    }
+
+    /// Adds a course and returns its ID.
+    #[inline]
+    pub fn add_course(&mut self, name: &str, archived: bool) -> String {
+        let id = self.next_id("course");
+        self.courses.push(Course {
+            archived,
+            id: id.clone(),
+            name: name.to_owned(),
+        });
+        id
+    }
+
+    /// Adds a take-home assignment with `questions` questions worth one point
+    /// each and returns a mutable reference to it for further tweaking.
+    #[inline]
+    pub fn add_assignment(
+        &mut self,
+        course_id: &str,
+        title: &str,
+        questions: usize,
+    ) -> &mut Assignment {
+        let id = self.next_id("assignment");
+        let exam_master_id = self.next_id("exam-master");
+        let questions = (1..=questions)
+            .map(|sequence| Question {
+                anchored: false,
+                comments: Vec::new(),
+                id: self.next_id("question"),
+                label: format!("Q{sequence}"),
+                points: 1.0,
+                score: None,
+                sequence,
+            })
+            .collect();
+
+        self.assignments.push(Assignment {
+            course_id: course_id.to_owned(),
+            drafting: false,
+            due: None,
+            exam_master_id,
+            id,
+            kind: ExamKind::AtHome,
+            late_due: None,
+            marks_sent_at: None,
+            normalized_points: None,
+            pages: Vec::new(),
+            questions,
+            submitted_at: None,
+            submitted_pages: Vec::new(),
+            title: title.to_owned(),
+        });
+        self.assignments
+            .last_mut()
+            .expect("assignment was just pushed")
+    }
+
+    /// Stores `image` as the grader's marked-up version of page `page_id`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no assignment has a page with that ID.
+    #[inline]
+    pub fn annotate_page(&mut self, page_id: &str, image: Vec<u8>) {
+        let key = format!("annotated/{page_id}");
+        let page = self
+            .assignments
+            .iter_mut()
+            .flat_map(|a| &mut a.pages)
+            .find(|p| p.id == page_id)
+            .expect("No page with that ID");
+        page.annotated_key = Some(key.clone());
+        self.s3_objects.insert(
+            key,
+            S3Object {
+                content_type: "image/jpeg".to_owned(),
+                data: image,
+            },
+        );
+    }
+
+    /// Finds an assignment by either its own ID or its exam master's ID.
+    #[inline]
+    pub fn assignment(&self, id: &str) -> Option<&Assignment> {
+        self.assignments
+            .iter()
+            .find(|a| a.id == id || a.exam_master_id == id)
+    }
+
+    /// Finds an assignment by either its own ID or its exam master's ID.
+    #[inline]
+    pub fn assignment_mut(&mut self, id: &str) -> Option<&mut Assignment> {
+        self.assignments
+            .iter_mut()
+            .find(|a| a.id == id || a.exam_master_id == id)
+    }
+}
@@ -0,0 +1,188 @@
+use crate::AssessmentKind;
+use crate::error::{CrowdmarkError, HttpError};
+use crate::ids::{AssessmentId, AssignmentId, PageId, QuestionId};
+use crate::jsonapi::{Document, RawResource};
+use crate::resources::{
+    AssignmentAttributes, ExamMasterAttributes, PageAttributes, QuestionAttributes,
+};
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use serde::Serialize;
+
+/// An assessment along with the authenticated student's assignment for it.
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct AssessmentDetail {
+    pub assignment_id: AssignmentId,
+    pub due: Option<DateTime<Utc>>,
+    pub id: AssessmentId,
+    pub kind: AssessmentKind,
+    pub late_due: Option<DateTime<Utc>>,
+    /// Questions in sequence order, each with its current draft pages.
+    pub questions: Vec<Question>,
+    pub state: SubmissionState,
+    pub submitted: Option<DateTime<Utc>>,
+    pub title: String,
+    /// Draft pages not anchored to any question.
+    pub unassigned_pages: Vec<DraftPage>,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct Question {
+    /// Exam page the question is anchored to, if any.
+    #[serde(skip)]
+    pub(crate) anchor: Option<String>,
+    pub id: QuestionId,
+    pub label: String,
+    pub max_points: Option<f32>,
+    /// Draft pages anchored to this question, in page order.
+    pub pages: Vec<DraftPage>,
+    pub sequence: usize,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct DraftPage {
+    pub filename: String,
+    pub id: PageId,
+    pub number: i64,
+    /// URL of the uploaded page image, if Crowdmark provided one.
+    pub url: Option<String>,
+    pub uuid: String,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum SubmissionState {
+    NotStarted,
+    Drafting,
+    Submitted,
+    Graded,
+}
+
+impl AssessmentDetail {
+    /// Iterates over every draft page, anchored or not.
+    #[inline]
+    pub fn pages(&self) -> impl Iterator<Item = &DraftPage> {
+        self.questions
+            .iter()
+            .flat_map(|question| &question.pages)
+            .chain(&self.unassigned_pages)
+    }
+}
+
+impl crate::Client {
+    /// Fetches the student's assignment for `assessment_id`, including its
+    /// exam master, questions, pages and, once graded, comments.
+    pub(crate) async fn fetch_assignment(
+        &self,
+        assessment_id: &AssessmentId,
+    ) -> Result<Document<RawResource>, CrowdmarkError> {
+        let resp = self
+            .send(|| {
+                Ok(self
+                    .client
+                    .get(self.endpoint(&format!("api/v2/student/assignments/{assessment_id}"))?)
+                    .query(&[
+                        ("fields[exam-masters][]", "type"),
+                        ("fields[exam-masters][]", "title"),
+                    ]))
+            })
+            .await?;
+
+        Ok(HttpError::check(resp, Method::GET).await?.json().await?)
+    }
+
+    /// Retrieves an assessment's questions, current draft and submission
+    /// state.
+    ///
+    /// # Arguments
+    ///
+    /// * `assessment_id` - The assessment to retrieve.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a [`CrowdmarkError`] if:
+    /// * The request to the Crowdmark API fails.
+    /// * The API returns an unexpected response format.
+    #[inline]
+    pub async fn get_assessment(
+        &self,
+        assessment_id: &AssessmentId,
+    ) -> Result<AssessmentDetail, CrowdmarkError> {
+        let document = self.fetch_assignment(assessment_id).await?;
+        let assignment = document.data::<AssignmentAttributes>()?;
+        let exam_master =
+            document.resolve::<ExamMasterAttributes>(&assignment.relationships.exam_master.data)?;
+
+        let mut questions: Vec<_> = document
+            .included::<QuestionAttributes>()?
+            .into_iter()
+            .map(|question| Question {
+                anchor: question
+                    .relationships
+                    .anchored_to_exam_page
+                    .and_then(|anchor| anchor.data)
+                    .map(|data| data.id),
+                id: QuestionId::from_trusted(question.id),
+                label: question.attributes.label.unwrap_or_default(),
+                max_points: question.attributes.points,
+                pages: Vec::new(),
+                sequence: question.attributes.sequence.unwrap_or_default(),
+            })
+            .collect();
+        questions.sort_by_key(|question| question.sequence);
+
+        let mut unassigned_pages = Vec::new();
+        for page in document.included::<PageAttributes>()? {
+            let question_id = page
+                .relationships
+                .question
+                .and_then(|question| question.data)
+                .map(|data| data.id);
+            let draft_page = DraftPage {
+                filename: page.attributes.filename.unwrap_or_default(),
+                id: PageId::from_trusted(page.id),
+                number: page.attributes.number.unwrap_or_default(),
+                url: page.attributes.url,
+                uuid: page.attributes.uuid.unwrap_or_default(),
+            };
+
+            match questions
+                .iter_mut()
+                .find(|question| Some(question.id.as_str()) == question_id.as_deref())
+            {
+                Some(question) => question.pages.push(draft_page),
+                None => unassigned_pages.push(draft_page),
+            }
+        }
+        for question in &mut questions {
+            question.pages.sort_by_key(|page| page.number);
+        }
+
+        let attributes = assignment.attributes;
+        let state = if attributes.marks_sent_at.is_some() {
+            SubmissionState::Graded
+        } else if attributes.submitted_at.is_some() {
+            SubmissionState::Submitted
+        } else if questions.iter().any(|q| !q.pages.is_empty()) || !unassigned_pages.is_empty() {
+            SubmissionState::Drafting
+        } else {
+            SubmissionState::NotStarted
+        };
+
+        Ok(AssessmentDetail {
+            assignment_id: AssignmentId::from_trusted(assignment.id),
+            due: attributes.due,
+            id: AssessmentId::from_trusted(exam_master.id),
+            kind: exam_master.attributes.kind.into(),
+            late_due: attributes.late_due,
+            questions,
+            state,
+            submitted: attributes.submitted_at,
+            title: exam_master.attributes.title,
+            unassigned_pages,
+        })
+    }
+}
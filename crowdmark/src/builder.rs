@@ -0,0 +1,251 @@
+use crate::Client;
+use crate::cookies::CookieJar;
+use crate::error::CrowdmarkError;
+use crate::rate_limit::RateLimiter;
+use crate::retry::RetryPolicy;
+use crate::session::{Session, SessionProvider, StaticSession};
+use reqwest::{Certificate, Proxy, Url};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub(crate) static DEFAULT_BASE_URL: &str = "https://app.crowdmark.com/";
+pub(crate) static DEFAULT_USER_AGENT: &str =
+    concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+
+/// Builder for configuring a [`Client`].
+#[derive(Clone)]
+#[must_use]
+pub struct ClientBuilder {
+    base_url: String,
+    exported_session: Option<String>,
+    /// Shared with the client a session provider logs in for, so that
+    /// logging in counts against the client's rate limit.
+    limiter: Option<Arc<RateLimiter>>,
+    proxies: Vec<Proxy>,
+    rate_limit: Option<(f64, u32)>,
+    pub(crate) retry_policy: RetryPolicy,
+    root_certificates: Vec<Certificate>,
+    session_provider: Option<Arc<dyn SessionProvider>>,
+    session_token: Option<String>,
+    timeout: Option<Duration>,
+    user_agent: String,
+}
+
+impl fmt::Debug for ClientBuilder {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("base_url", &self.base_url)
+            .field("proxies", &self.proxies)
+            .field("rate_limit", &self.rate_limit)
+            .field("retry_policy", &self.retry_policy)
+            .field("root_certificates", &self.root_certificates)
+            .field("session_provider", &self.session_provider)
+            .field("timeout", &self.timeout)
+            .field("user_agent", &self.user_agent)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ClientBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            exported_session: None,
+            limiter: None,
+            proxies: Vec::new(),
+            rate_limit: None,
+            retry_policy: RetryPolicy::default(),
+            root_certificates: Vec::new(),
+            session_provider: None,
+            session_token: None,
+            timeout: None,
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Creates a new [`ClientBuilder`] pointing at the public Crowdmark instance.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the base URL every endpoint is resolved against.
+    ///
+    /// Defaults to `https://app.crowdmark.com/`.
+    #[inline]
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        base_url.clone_into(&mut self.base_url);
+        self
+    }
+
+    /// Starts from the cookies saved with
+    /// [`Client::export_session`] instead of an empty cookie jar. A token
+    /// given to [`session_token`](Self::session_token) replaces the saved
+    /// session.
+    #[inline]
+    pub fn exported_session(mut self, session: &str) -> Self {
+        self.exported_session = Some(session.to_owned());
+        self
+    }
+
+    /// Routes every request through `proxy`. May be called more than once.
+    #[inline]
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxies.push(proxy);
+        self
+    }
+
+    /// Limits the client, and every clone of it, to `per_second` requests
+    /// per second on average, allowing bursts of up to `burst` requests.
+    /// Requests over the limit wait for their turn.
+    ///
+    /// Unlimited by default.
+    #[inline]
+    pub fn rate_limit(mut self, per_second: f64, burst: u32) -> Self {
+        self.rate_limit = Some((per_second, burst));
+        self
+    }
+
+    /// Sets how requests that fail for a transient reason are retried.
+    ///
+    /// Defaults to [`RetryPolicy::new`].
+    #[inline]
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Trusts `certificate` in addition to the system roots. May be called
+    /// more than once.
+    #[inline]
+    pub fn root_certificate(mut self, certificate: Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Sets where session tokens come from, both for the first request and
+    /// whenever Crowdmark rejects the current one.
+    ///
+    /// Defaults to a [`StaticSession`] for the token given to
+    /// [`session_token`](Self::session_token), if any.
+    #[inline]
+    pub fn session_provider<P: SessionProvider + 'static>(mut self, provider: P) -> Self {
+        self.session_provider = Some(Arc::new(provider));
+        self
+    }
+
+    /// Sets the `cm_session_id` cookie sent with requests to Crowdmark until
+    /// it expires or Crowdmark replaces it. The session provider, if any, is only asked for a token
+    /// after that.
+    #[inline]
+    pub fn session_token(mut self, session_token: &str) -> Self {
+        self.session_token = Some(session_token.to_owned());
+        self
+    }
+
+    /// Sets the total timeout applied to each request.
+    #[inline]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    #[inline]
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        user_agent.clone_into(&mut self.user_agent);
+        self
+    }
+
+    /// Builds the [`Client`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the base URL cannot be parsed, the
+    /// session token is not a valid header value, the exported session
+    /// cannot be read or the HTTP client fails to initialize.
+    #[inline]
+    pub fn build(self) -> Result<Client, CrowdmarkError> {
+        let base_url = self.parsed_base_url()?;
+        let cookies = Arc::new(match &self.exported_session {
+            Some(session) => CookieJar::import(session)?,
+            None => CookieJar::default(),
+        });
+        if let Some(session_token) = &self.session_token {
+            cookies.set_session_token(&base_url, session_token)?;
+        }
+        let limiter = self.limiter();
+        let provider = self.session_provider.clone().or_else(|| {
+            self.session_token
+                .as_deref()
+                .map(|token| Arc::new(StaticSession::new(token)) as Arc<dyn SessionProvider>)
+        });
+        let session = provider.map(|provider| {
+            Arc::new(Session {
+                builder: Self {
+                    limiter: limiter.clone(),
+                    ..self.clone()
+                },
+                login: tokio::sync::Mutex::default(),
+                provider,
+                validated: Mutex::default(),
+            })
+        });
+
+        let client = self
+            .http_client()
+            .cookie_provider(Arc::<CookieJar>::clone(&cookies))
+            .build()?;
+
+        Ok(Client {
+            base_url,
+            client,
+            cookies,
+            csrf: Arc::default(),
+            limiter,
+            retry: self.retry_policy,
+            session,
+        })
+    }
+
+    /// Returns a [`reqwest::ClientBuilder`] with the user agent, timeout,
+    /// proxies and root certificates applied.
+    pub(crate) fn http_client(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .tls_certs_merge(self.root_certificates.iter().cloned());
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        for proxy in &self.proxies {
+            builder = builder.proxy(proxy.clone());
+        }
+        builder
+    }
+
+    /// Returns the rate limiter of the client this builder logs in for, or
+    /// a new one if there is no such client yet.
+    pub(crate) fn limiter(&self) -> Option<Arc<RateLimiter>> {
+        self.limiter.clone().or_else(|| {
+            self.rate_limit
+                .filter(|&(per_second, _)| per_second > 0.0)
+                .map(|(per_second, burst)| Arc::new(RateLimiter::new(per_second, burst)))
+        })
+    }
+
+    /// Parses the base URL, making sure it ends with a `/` so relative
+    /// endpoints are joined under it rather than replacing its last segment.
+    pub(crate) fn parsed_base_url(&self) -> Result<Url, CrowdmarkError> {
+        let mut base_url = Url::parse(&self.base_url)?;
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        Ok(base_url)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::CrowdmarkError;
+use chrono::{DateTime, Utc};
+use cookie_store::{CookieExpiration, CookieStore};
+use reqwest::Url;
+use reqwest::header::HeaderValue;
+use std::fmt;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Name of the cookie holding the Crowdmark session.
+static SESSION_COOKIE: &str = "cm_session_id";
+
+/// Cookies shared by every clone of a [`Client`](crate::Client), updated by
+/// every `Set-Cookie` Crowdmark sends.
+#[derive(Default)]
+pub(crate) struct CookieJar {
+    store: RwLock<CookieStore>,
+}
+
+impl fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieJar").finish_non_exhaustive()
+    }
+}
+
+impl CookieJar {
+    /// Loads cookies saved by [`export`](Self::export), dropping those that
+    /// have expired since.
+    pub(crate) fn import(json: &str) -> Result<Self, CrowdmarkError> {
+        let store =
+            cookie_store::serde::json::load(json.as_bytes()).map_err(CrowdmarkError::Session)?;
+        Ok(Self {
+            store: RwLock::new(store),
+        })
+    }
+
+    /// Saves every cookie as JSON, including session cookies, which have no
+    /// expiry but are what keeps the client signed in.
+    pub(crate) fn export(&self) -> Result<String, CrowdmarkError> {
+        let mut json = Vec::new();
+        cookie_store::serde::json::save_incl_expired_and_nonpersistent(&self.read(), &mut json)
+            .map_err(CrowdmarkError::Session)?;
+        String::from_utf8(json).map_err(|err| CrowdmarkError::Session(err.into()))
+    }
+
+    /// Returns the session token sent along to `url`, if any.
+    pub(crate) fn session_token(&self, url: &Url) -> Option<String> {
+        self.read()
+            .get_request_values(url)
+            .find(|&(name, _)| name == SESSION_COOKIE)
+            .map(|(_, value)| value.to_owned())
+    }
+
+    /// Returns the session token sent along to `url`, if any, and when its
+    /// cookie expires, unless it lasts for the session.
+    pub(crate) fn session(&self, url: &Url) -> Option<(String, Option<DateTime<Utc>>)> {
+        let store = self.read();
+        let cookie = store
+            .matches(url)
+            .into_iter()
+            .find(|cookie| cookie.name() == SESSION_COOKIE)?;
+        let expires = match cookie.expires {
+            CookieExpiration::AtUtc(expires) => {
+                DateTime::from_timestamp(expires.unix_timestamp(), 0)
+            }
+            CookieExpiration::SessionEnd => None,
+        };
+        Some((cookie.value().to_owned(), expires))
+    }
+
+    /// Sends `token` as the session to every path on `url`'s host.
+    pub(crate) fn set_session_token(&self, url: &Url, token: &str) -> Result<(), CrowdmarkError> {
+        HeaderValue::from_str(token)?;
+        self.write()
+            .parse(&format!("{SESSION_COOKIE}={token}; Path=/"), url)
+            .map_err(|err| CrowdmarkError::Session(err.into()))?;
+        Ok(())
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, CookieStore> {
+        self.store.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, CookieStore> {
+        self.store.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let mut store = self.write();
+        for header in cookie_headers.filter_map(|header| header.to_str().ok()) {
+            // As in a browser, cookies that are malformed or not allowed for
+            // `url` are ignored.
+            store.parse(header, url).ok();
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .read()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        let mut value = HeaderValue::from_str(&header).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+impl crate::Client {
+    /// Creates a [`Client`](crate::Client) for the public Crowdmark instance
+    /// from cookies saved with [`export_session`](Self::export_session).
+    ///
+    /// Use [`ClientBuilder::exported_session`](crate::ClientBuilder::exported_session)
+    /// to configure anything else.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if `session` is not an exported session.
+    #[inline]
+    pub fn from_session(session: &str) -> Result<Self, CrowdmarkError> {
+        crate::ClientBuilder::new()
+            .exported_session(session)
+            .build()
+    }
+
+    /// Saves the client's cookies, including any Crowdmark has refreshed
+    /// since, as JSON that [`from_session`](Self::from_session) reads back.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the cookies cannot be serialized.
+    #[inline]
+    pub fn export_session(&self) -> Result<String, CrowdmarkError> {
+        self.cookies.export()
+    }
+}
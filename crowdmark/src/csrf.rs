@@ -0,0 +1,29 @@
+use crate::error::{CrowdmarkError, HttpError};
+use regex_lite::Regex;
+use reqwest::Method;
+
+impl crate::Client {
+    /// Returns the cached CSRF token, fetching it first if there is none yet
+    /// or if the cached one is `stale`, i.e. Crowdmark has just rejected it.
+    pub(crate) async fn csrf_token(&self, stale: Option<&str>) -> Result<String, CrowdmarkError> {
+        // Holding the lock while fetching makes concurrent callers wait for
+        // one fetch instead of each starting their own.
+        let mut cached = self.csrf.lock().await;
+        if let Some(token) = cached.as_deref()
+            && Some(token) != stale
+        {
+            return Ok(token.to_owned());
+        }
+
+        let resp = self
+            .send(|| Ok(self.client.get(self.endpoint("student")?)))
+            .await?;
+        let re = Regex::new(r#"<meta name="csrf-token" content="([^"]+)""#)?;
+        let token = re
+            .captures(&HttpError::check(resp, Method::GET).await?.text().await?)
+            .map(|captures| captures[1].to_string())
+            .ok_or_else(|| CrowdmarkError::NotAuthenticated("Missing CSRF Token".to_owned()))?;
+        *cached = Some(token.clone());
+        Ok(token)
+    }
+}
@@ -0,0 +1,137 @@
+use crate::error::{CrowdmarkError, HttpError};
+use crate::ids::{AssessmentId, AssignmentId, PageId, QuestionId};
+use reqwest::Method;
+
+impl crate::Client {
+    async fn update_draft_page(
+        &self,
+        page_id: &PageId,
+        data: serde_json::Value,
+    ) -> Result<(), CrowdmarkError> {
+        let request = self
+            .client
+            .patch(self.endpoint(&format!("api/v2/student/assignment-pages/{page_id}"))?)
+            .header("Content-Type", "application/vnd.api+json")
+            .json(&serde_json::json!({ "data": data }));
+        HttpError::check(self.execute(request).await?, Method::PATCH).await?;
+        Ok(())
+    }
+
+    /// Removes a page from its draft.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the request to Crowdmark fails.
+    #[inline]
+    pub async fn delete_draft_page(&self, page_id: &PageId) -> Result<(), CrowdmarkError> {
+        self.update_draft_page(
+            page_id,
+            serde_json::json!({
+                "id": page_id,
+                "type": "assignment-pages",
+                "attributes": { "state": "pending_delete" },
+                "relationships": {
+                    "question": { "data": { "type": "assignment-questions", "id": "" } }
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Anchors a draft page to another question.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the request to Crowdmark fails.
+    #[inline]
+    pub async fn move_draft_page(
+        &self,
+        page_id: &PageId,
+        question_id: &QuestionId,
+    ) -> Result<(), CrowdmarkError> {
+        self.update_draft_page(
+            page_id,
+            serde_json::json!({
+                "id": page_id,
+                "type": "assignment-pages",
+                "relationships": {
+                    "question": { "data": { "type": "assignment-questions", "id": question_id } }
+                }
+            }),
+        )
+        .await
+    }
+
+    /// Renumbers the draft pages of `assessment_id` so that `page_ids` come
+    /// first, in the order given, followed by the remaining pages in their
+    /// current order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if a page is not in the draft or a request
+    /// to Crowdmark fails.
+    #[inline]
+    pub async fn reorder_draft_pages(
+        &self,
+        assessment_id: &AssessmentId,
+        page_ids: &[PageId],
+    ) -> Result<(), CrowdmarkError> {
+        let draft = self.get_assessment(assessment_id).await?;
+        let mut pages: Vec<_> = draft.pages().collect();
+        if let Some(page_id) = page_ids
+            .iter()
+            .find(|page_id| !pages.iter().any(|page| page.id == **page_id))
+        {
+            return Err(CrowdmarkError::UnknownPage(page_id.to_string()));
+        }
+        pages.sort_by_key(|page| page.number);
+        let rest = pages
+            .iter()
+            .map(|page| &page.id)
+            .filter(|page_id| !page_ids.contains(page_id));
+
+        for (number, page_id) in (1_usize..).zip(page_ids.iter().chain(rest)) {
+            self.update_draft_page(
+                page_id,
+                serde_json::json!({
+                    "id": page_id,
+                    "type": "assignment-pages",
+                    "attributes": { "number": number }
+                }),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Anchors a question to `exam_page`, or detaches it from the page it
+    /// is anchored to if `None`.
+    pub(crate) async fn anchor_question(
+        &self,
+        assignment_id: &AssignmentId,
+        question_id: &QuestionId,
+        exam_page: Option<&str>,
+    ) -> Result<(), CrowdmarkError> {
+        let anchor = exam_page.map(|id| serde_json::json!({ "id": id, "type": "exam-pages" }));
+        let body = serde_json::json!({
+            "data": {
+                "id": question_id,
+                "type": "assignment-questions",
+                "relationships": {
+                    "anchored-to-exam-page": { "data": anchor },
+                    "assignment": { "data": { "id": assignment_id, "type": "assignments" } }
+                }
+            }
+        });
+
+        let request = self
+            .client
+            .patch(self.endpoint(&format!(
+                "api/v2/student/assignment-questions/{question_id}"
+            ))?)
+            .header("Content-Type", "application/vnd.api+json")
+            .json(&body);
+        HttpError::check(self.execute(request).await?, Method::PATCH).await?;
+        Ok(())
+    }
+}
@@ -1,36 +1,179 @@
+use reqwest::{Method, Response, StatusCode};
 use thiserror::Error;
 
+/// Longest response body excerpt kept in an [`HttpError`], in characters.
+const BODY_EXCERPT_LENGTH: usize = 300;
+
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum CrowdmarkError {
     #[error("Failed to submit Crowdmark assessment")]
-    AssessmentSubmit(String),
+    AssessmentSubmit(#[source] HttpError),
     #[error("Failed to upload to Crowdmark assessment")]
-    AssessmentUpload(String),
-    #[error("JSON error")]
+    AssessmentUpload(#[source] HttpError),
+    #[error("Missing included {kind} resource with ID {id}")]
+    DanglingRelationship { id: String, kind: String },
+    #[error("JSON error: {0}")]
     Decode(String),
+    #[error("HTTP request failed")]
+    Http(#[from] HttpError),
     #[error("Invalid assessment ID")]
     InvalidAssessmentID(),
+    #[error("Invalid assignment ID")]
+    InvalidAssignmentID(),
     #[error("Invalid course ID")]
     InvalidCourseID(),
     #[error("Invalid header value")]
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+    #[error("Invalid page ID")]
+    InvalidPageID(),
+    #[error("Invalid page map entry {0:?}")]
+    InvalidPageMap(String),
+    #[error("Invalid question ID")]
+    InvalidQuestionID(),
     #[error("Tokio join error")]
     Join(#[from] tokio::task::JoinError),
+    #[error("Failed to access upload journal")]
+    Journal(#[from] std::io::Error),
+    #[cfg(feature = "keyring")]
+    #[error("Failed to access keyring")]
+    Keyring(#[from] keyring::Error),
     #[error("Failed to login")]
     Login(),
-    #[error("Not authenticated")]
+    #[error("Page {0} does not exist")]
+    MissingPage(usize),
+    #[error("Not authenticated: {0}")]
     NotAuthenticated(String),
+    #[error("Assessment has not been graded yet")]
+    NotGraded(),
     #[error("Regex compile error")]
     Regex(#[from] regex_lite::Error),
     #[error("Request error")]
     Reqwest(#[source] reqwest::Error),
+    #[error("{error}; restoring the previous draft also failed: {rollback}")]
+    RollbackFailed {
+        error: Box<CrowdmarkError>,
+        rollback: Box<CrowdmarkError>,
+    },
     #[error("Invalid S3 Policy Response")]
     S3Policy(),
     #[error("Failed to upload to S3")]
-    S3Upload(String),
+    S3Upload(#[source] HttpError),
+    #[error("Invalid exported session")]
+    Session(#[source] Box<dyn std::error::Error + Send + Sync>),
     #[error("Too many pages submitted")]
     TooManyPages(),
+    #[error("Page {0} is not in the draft")]
+    UnknownPage(String),
+    #[error("Question {0} does not exist")]
+    UnknownQuestion(usize),
+    #[error("Invalid URL")]
+    Url(#[from] url::ParseError),
+}
+
+impl CrowdmarkError {
+    /// Classifies the error, or returns `None` if it is neither a failed
+    /// request nor an authentication problem.
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self {
+            Self::AssessmentSubmit(err)
+            | Self::AssessmentUpload(err)
+            | Self::Http(err)
+            | Self::S3Upload(err) => Some(err.kind()),
+            Self::Login() | Self::NotAuthenticated(_) => Some(ErrorKind::Auth),
+            Self::Reqwest(err) if err.is_connect() || err.is_timeout() => {
+                Some(ErrorKind::Retryable)
+            }
+            Self::RollbackFailed { error, .. } => error.kind(),
+            _ => None,
+        }
+    }
+}
+
+/// A request that Crowdmark or S3 answered with an error status.
+#[derive(Debug, Error)]
+#[error("{method} {path} returned {status}{}", excerpt(body))]
+#[non_exhaustive]
+pub struct HttpError {
+    /// The start of the response body.
+    pub body: String,
+    pub method: Method,
+    /// Path of the request URL, without its query.
+    pub path: String,
+    pub status: StatusCode,
+}
+
+impl HttpError {
+    /// Returns `response` if its status is a success, and otherwise reads
+    /// its body into an [`HttpError`] for the `method` request.
+    pub(crate) async fn check(response: Response, method: Method) -> Result<Response, Self> {
+        let status = response.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(response);
+        }
+        let path = response.url().path().to_owned();
+        let body = response.text().await.unwrap_or_default();
+        let body = body.trim();
+        let mut excerpt: String = body.chars().take(BODY_EXCERPT_LENGTH).collect();
+        if excerpt.len() < body.len() {
+            excerpt.push('…');
+        }
+        Err(Self {
+            body: excerpt,
+            method,
+            path,
+            status,
+        })
+    }
+
+    /// Classifies the error by its status.
+    #[inline]
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        ErrorKind::of(self.status)
+    }
+}
+
+/// Formats a response body excerpt for appending to a message.
+fn excerpt(body: &str) -> String {
+    if body.is_empty() {
+        String::new()
+    } else {
+        format!(": {body}")
+    }
+}
+
+/// Broad cause of a failed request, for deciding how to react to it.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The session is missing or has expired.
+    Auth,
+    /// The request was rejected, for instance as forbidden, and will fail
+    /// again if repeated.
+    Client,
+    /// A transient failure; the same request may succeed later.
+    Retryable,
+    /// Crowdmark or S3 failed in a way retrying is unlikely to fix.
+    Server,
+}
+
+impl ErrorKind {
+    pub(crate) fn of(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => Self::Auth,
+            StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Self::Retryable,
+            status if status.is_client_error() => Self::Client,
+            _ => Self::Server,
+        }
+    }
 }
 
 impl From<reqwest::Error> for CrowdmarkError {
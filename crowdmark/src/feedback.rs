@@ -0,0 +1,143 @@
+use crate::error::CrowdmarkError;
+use crate::ids::{AssessmentId, PageId, QuestionId};
+use crate::resources::{
+    AssignmentAttributes, CommentAttributes, ExamMasterAttributes, PageAttributes,
+    QuestionAttributes,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Grading feedback for an assessment whose marks have been sent back.
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct Feedback {
+    pub assessment_id: AssessmentId,
+    pub graded: DateTime<Utc>,
+    /// Questions in sequence order.
+    pub questions: Vec<QuestionFeedback>,
+    pub score: Option<f32>,
+    pub title: String,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct QuestionFeedback {
+    pub comments: Vec<String>,
+    pub id: QuestionId,
+    pub label: String,
+    pub max_points: Option<f32>,
+    /// Marked-up pages for this question, in page order.
+    pub pages: Vec<AnnotatedPage>,
+    pub points: Option<f32>,
+    pub sequence: usize,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct AnnotatedPage {
+    pub id: PageId,
+    pub number: i64,
+    /// URL of the page image with the grader's annotations.
+    pub url: String,
+}
+
+impl crate::Client {
+    /// Retrieves per-question scores, grader comments and marked-up pages
+    /// for a graded assessment.
+    ///
+    /// # Arguments
+    ///
+    /// * `assessment_id` - The assessment to retrieve feedback for.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a [`CrowdmarkError`] if:
+    /// * The assessment's marks have not been sent yet.
+    /// * The request to the Crowdmark API fails.
+    /// * The API returns an unexpected response format.
+    #[inline]
+    pub async fn get_feedback(
+        &self,
+        assessment_id: &AssessmentId,
+    ) -> Result<Feedback, CrowdmarkError> {
+        let document = self.fetch_assignment(assessment_id).await?;
+        let assignment = document.data::<AssignmentAttributes>()?;
+        let graded = assignment
+            .attributes
+            .marks_sent_at
+            .ok_or(CrowdmarkError::NotGraded())?;
+        let exam_master =
+            document.resolve::<ExamMasterAttributes>(&assignment.relationships.exam_master.data)?;
+
+        let mut questions: Vec<_> = document
+            .included::<QuestionAttributes>()?
+            .into_iter()
+            .map(|question| QuestionFeedback {
+                comments: Vec::new(),
+                id: QuestionId::from_trusted(question.id),
+                label: question.attributes.label.unwrap_or_default(),
+                max_points: question.attributes.points,
+                pages: Vec::new(),
+                points: question.attributes.score,
+                sequence: question.attributes.sequence.unwrap_or_default(),
+            })
+            .collect();
+        questions.sort_by_key(|question| question.sequence);
+
+        for comment in document.included::<CommentAttributes>()? {
+            let question_id = &comment.relationships.question.data.id;
+            if let Some(question) = questions
+                .iter_mut()
+                .find(|question| question.id.as_str() == question_id)
+            {
+                question.comments.push(comment.attributes.body);
+            }
+        }
+
+        for page in document.included::<PageAttributes>()? {
+            let (Some(url), Some(question_id)) = (
+                page.attributes.marked_url,
+                page.relationships
+                    .question
+                    .and_then(|question| question.data)
+                    .map(|data| data.id),
+            ) else {
+                continue;
+            };
+            if let Some(question) = questions
+                .iter_mut()
+                .find(|question| question.id.as_str() == question_id)
+            {
+                question.pages.push(AnnotatedPage {
+                    id: PageId::from_trusted(page.id),
+                    number: page.attributes.number.unwrap_or_default(),
+                    url,
+                });
+            }
+        }
+        for question in &mut questions {
+            question.pages.sort_by_key(|page| page.number);
+        }
+
+        Ok(Feedback {
+            assessment_id: AssessmentId::from_trusted(exam_master.id),
+            graded,
+            questions,
+            score: assignment.attributes.normalized_points,
+            title: exam_master.attributes.title,
+        })
+    }
+
+    /// Downloads the marked-up image for `page`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the image cannot be downloaded.
+    #[inline]
+    pub async fn download_annotated_page(
+        &self,
+        page: &AnnotatedPage,
+    ) -> Result<Vec<u8>, CrowdmarkError> {
+        self.download(&page.url).await
+    }
+}
@@ -0,0 +1,112 @@
+//! Strongly typed Crowdmark identifiers.
+//!
+//! Crowdmark uses several kinds of opaque string IDs that are easy to mix up.
+//! In particular, an [`AssessmentId`] identifies the exam master shared by the
+//! whole class, while an [`AssignmentId`] identifies one student's copy of it.
+
+use crate::error::CrowdmarkError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident => $error:expr) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl $name {
+            /// Returns the ID as a string slice.
+            #[inline]
+            #[must_use]
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Wraps an ID received from Crowdmark without validating it.
+            pub(crate) fn from_trusted(id: String) -> Self {
+                Self(id)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = CrowdmarkError;
+
+            #[inline]
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                if is_valid(s) {
+                    Ok(Self(s.to_owned()))
+                } else {
+                    Err($error)
+                }
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = CrowdmarkError;
+
+            #[inline]
+            fn try_from(s: String) -> Result<Self, Self::Error> {
+                if is_valid(&s) { Ok(Self(s)) } else { Err($error) }
+            }
+        }
+
+        impl From<$name> for String {
+            #[inline]
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            #[inline]
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            #[inline]
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+    };
+}
+
+id_type! {
+    /// Identifies a course.
+    CourseId => CrowdmarkError::InvalidCourseID()
+}
+
+id_type! {
+    /// Identifies an assessment (a Crowdmark "exam master"), as shown by
+    /// `list_assessments`.
+    AssessmentId => CrowdmarkError::InvalidAssessmentID()
+}
+
+id_type! {
+    /// Identifies the authenticated student's assignment for an assessment.
+    AssignmentId => CrowdmarkError::InvalidAssignmentID()
+}
+
+id_type! {
+    /// Identifies an uploaded page within an assignment.
+    PageId => CrowdmarkError::InvalidPageID()
+}
+
+id_type! {
+    /// Identifies a question within an assignment.
+    QuestionId => CrowdmarkError::InvalidQuestionID()
+}
+
+/// IDs are interpolated into URL paths, so only accept characters that
+/// cannot change the meaning of the path.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && !id.chars().all(|c| c == '.')
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
@@ -0,0 +1,108 @@
+use crate::error::CrowdmarkError;
+use crate::ids::{PageId, QuestionId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::fmt::Write as _;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Record of the page images an upload has sent to S3 and the assignment
+/// pages created from them so far, saved after every step so that an
+/// interrupted upload can be resumed.
+#[derive(Debug)]
+pub(crate) struct Journal {
+    entries: Mutex<Vec<JournalEntry>>,
+    path: PathBuf,
+}
+
+/// Progress of one page, identified by its image hash, question and number.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct JournalEntry {
+    pub hash: String,
+    pub number: usize,
+    /// Set once the assignment page has been created.
+    pub page_id: Option<PageId>,
+    pub question_id: QuestionId,
+    pub s3_key: String,
+    /// UUID the image was uploaded to S3 under.
+    pub uuid: String,
+}
+
+impl Journal {
+    /// Loads the journal at `path`, or starts an empty one if it does not
+    /// exist yet.
+    pub(crate) fn open(path: &Path) -> Result<Self, CrowdmarkError> {
+        let entries = match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Self {
+            entries: Mutex::new(entries),
+            path: path.to_owned(),
+        })
+    }
+
+    /// Whether an earlier attempt created the page `page_id`.
+    pub(crate) fn contains(&self, page_id: &PageId) -> bool {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .any(|entry| entry.page_id.as_ref() == Some(page_id))
+    }
+
+    /// Finds the entry for `number`'s image with `hash` in `question_id`.
+    pub(crate) fn find(
+        &self,
+        hash: &str,
+        question_id: &QuestionId,
+        number: usize,
+    ) -> Option<JournalEntry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|entry| {
+                entry.hash == hash && entry.question_id == *question_id && entry.number == number
+            })
+            .cloned()
+    }
+
+    /// Adds or updates the entry for a page and saves the journal.
+    pub(crate) fn record(&self, entry: JournalEntry) -> Result<(), CrowdmarkError> {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries.retain(|other| {
+            other.hash != entry.hash
+                || other.question_id != entry.question_id
+                || other.number != entry.number
+        });
+        entries.push(entry);
+
+        // Write to a temporary file first so an interruption never leaves a
+        // truncated journal behind.
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, serde_json::to_vec_pretty(&*entries)?)?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    /// Deletes the journal once the upload it describes has completed.
+    pub(crate) fn remove(&self) -> Result<(), CrowdmarkError> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Hex-encoded SHA-256 of a page image.
+pub(crate) fn hash(img: &[u8]) -> String {
+    Sha256::digest(img)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
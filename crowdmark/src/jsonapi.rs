@@ -0,0 +1,212 @@
+//! Minimal [JSON:API](https://jsonapi.org) document parsing.
+//!
+//! Responses are first decoded into untyped [`RawResource`]s, which are then
+//! parsed on demand into typed [`Resource`]s through the [`Attributes`] trait.
+//! Declaring a new resource is a matter of writing its attribute and
+//! relationship structs and tying them together with an [`Attributes`] impl.
+
+use crate::error::CrowdmarkError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Attributes of a JSON:API resource of type [`Attributes::TYPE`].
+pub(crate) trait Attributes: DeserializeOwned {
+    /// The resource's `type` member.
+    const TYPE: &'static str;
+
+    /// The resource's `relationships` member.
+    type Relationships: DeserializeOwned;
+}
+
+/// A top-level JSON:API document whose primary data is `D`.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Document<D> {
+    data: D,
+    #[serde(default)]
+    included: Vec<RawResource>,
+}
+
+/// A resource object whose attributes and relationships have not been
+/// parsed yet.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct RawResource {
+    #[serde(default = "empty_object")]
+    attributes: Value,
+    #[serde(deserialize_with = "string_or_number")]
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "empty_object")]
+    relationships: Value,
+}
+
+/// A resource object parsed as `A`.
+#[derive(Clone, Debug)]
+pub(crate) struct Resource<A: Attributes> {
+    pub attributes: A,
+    pub id: String,
+    pub relationships: A::Relationships,
+}
+
+/// A relationship object. `T` is [`Identifier`] for to-one relationships,
+/// `Option<Identifier>` for nullable ones and `Vec<Identifier>` for to-many.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Relationship<T> {
+    pub data: T,
+}
+
+/// A resource identifier object.
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct Identifier {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Placeholder for resources whose attributes or relationships are unused.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub(crate) struct Empty {}
+
+impl RawResource {
+    /// Parses this resource as `A`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError::Decode`] if the resource is not of type
+    /// [`Attributes::TYPE`] or does not match `A`.
+    pub(crate) fn parse<A: Attributes>(&self) -> Result<Resource<A>, CrowdmarkError> {
+        if self.kind != A::TYPE {
+            return Err(CrowdmarkError::Decode(format!(
+                "Expected {} resource but found {} {}",
+                A::TYPE,
+                self.kind,
+                self.id
+            )));
+        }
+
+        Ok(Resource {
+            attributes: A::deserialize(&self.attributes)?,
+            id: self.id.clone(),
+            relationships: A::Relationships::deserialize(&self.relationships)?,
+        })
+    }
+}
+
+impl Document<RawResource> {
+    /// Parses the single primary resource as `A`.
+    pub(crate) fn data<A: Attributes>(&self) -> Result<Resource<A>, CrowdmarkError> {
+        self.data.parse()
+    }
+}
+
+impl Document<Vec<RawResource>> {
+    /// Parses every primary resource as `A`.
+    pub(crate) fn data<A: Attributes>(&self) -> Result<Vec<Resource<A>>, CrowdmarkError> {
+        self.data.iter().map(RawResource::parse).collect()
+    }
+}
+
+impl<D> Document<D> {
+    /// Parses every included resource of type [`Attributes::TYPE`] as `A`.
+    pub(crate) fn included<A: Attributes>(&self) -> Result<Vec<Resource<A>>, CrowdmarkError> {
+        self.included
+            .iter()
+            .filter(|resource| resource.kind == A::TYPE)
+            .map(RawResource::parse)
+            .collect()
+    }
+
+    /// Looks up the included resource `identifier` points to and parses it
+    /// as `A`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError::DanglingRelationship`] if no such resource
+    /// was included, or [`CrowdmarkError::Decode`] if it does not match `A`.
+    pub(crate) fn resolve<A: Attributes>(
+        &self,
+        identifier: &Identifier,
+    ) -> Result<Resource<A>, CrowdmarkError> {
+        self.included
+            .iter()
+            .find(|resource| resource.kind == identifier.kind && resource.id == identifier.id)
+            .ok_or_else(|| CrowdmarkError::DanglingRelationship {
+                id: identifier.id.clone(),
+                kind: identifier.kind.clone(),
+            })?
+            .parse()
+    }
+}
+
+fn empty_object() -> Value {
+    Value::Object(serde_json::Map::new())
+}
+
+/// Crowdmark serializes some IDs as numbers and others as strings.
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Value::deserialize(deserializer)? {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        other => Err(serde::de::Error::custom(format!(
+            "expected string or number ID, found {other}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Attributes, Document, Empty, Identifier, RawResource, Relationship};
+    use crate::error::CrowdmarkError;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Assignment {}
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct AssignmentRelationships {
+        exam_master: Relationship<Identifier>,
+    }
+
+    impl Attributes for Assignment {
+        const TYPE: &'static str = "assignments";
+        type Relationships = AssignmentRelationships;
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ExamMaster {}
+
+    impl Attributes for ExamMaster {
+        const TYPE: &'static str = "exam-masters";
+        type Relationships = Empty;
+    }
+
+    #[test]
+    fn resolving_a_missing_included_resource_names_it() {
+        let document: Document<RawResource> = serde_json::from_value(serde_json::json!({
+            "data": {
+                "id": "1",
+                "type": "assignments",
+                "relationships": {
+                    "exam-master": { "data": { "id": 7, "type": "exam-masters" } }
+                }
+            },
+            "included": [{ "id": "8", "type": "exam-masters" }]
+        }))
+        .expect("Invalid document");
+        let assignment = document.data::<Assignment>().expect("Invalid assignment");
+
+        let result = document.resolve::<ExamMaster>(&assignment.relationships.exam_master.data);
+
+        assert!(matches!(
+            result,
+            Err(CrowdmarkError::DanglingRelationship { id, kind })
+                if id == "7" && kind == "exam-masters"
+        ));
+    }
+}
@@ -1,19 +1,75 @@
+mod assessment;
+mod builder;
+mod cookies;
+mod csrf;
+mod draft;
 pub mod error;
+mod feedback;
+mod ids;
+mod journal;
+mod jsonapi;
 pub mod login;
+mod page_map;
+mod progress;
+mod rate_limit;
+mod resources;
+mod retry;
+mod session;
+mod submission;
 mod upload;
 
+pub use assessment::{AssessmentDetail, DraftPage, Question, SubmissionState};
+pub use builder::ClientBuilder;
+pub use feedback::{AnnotatedPage, Feedback, QuestionFeedback};
+pub use ids::{AssessmentId, AssignmentId, CourseId, PageId, QuestionId};
+pub use page_map::PageMap;
+pub use progress::UploadEvent;
+pub use retry::RetryPolicy;
+#[cfg(feature = "keyring")]
+pub use session::KeyringSession;
+pub use session::{PasswordSession, SessionFuture, SessionProvider, StaticSession};
+pub use submission::{Submission, SubmittedPage, SubmittedQuestion};
+pub use upload::{UploadMode, UploadOptions};
+
 use chrono::{DateTime, Utc};
-use error::CrowdmarkError;
-use regex_lite::Regex;
-use reqwest::header;
+use cookies::CookieJar;
+use error::{CrowdmarkError, HttpError};
+use jsonapi::{Document, Identifier, RawResource, Relationship};
+use rate_limit::RateLimiter;
+use reqwest::{Method, RequestBuilder, Response, ResponseBuilderExt as _, StatusCode, Url};
+use resources::{AssignmentAttributes, ExamMasterAttributes};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use session::Session;
+use std::fmt;
+use std::sync::Arc;
 
-static DEFAULT_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
+/// Header Crowdmark expects the CSRF token in.
+static CSRF_HEADER: &str = "X-Csrf-Token";
 
-#[derive(Debug)]
+#[derive(Clone)]
 pub struct Client {
+    base_url: Url,
     client: reqwest::Client,
+    cookies: Arc<CookieJar>,
+    /// CSRF token for state-changing requests, fetched when first needed.
+    csrf: Arc<tokio::sync::Mutex<Option<String>>>,
+    limiter: Option<Arc<RateLimiter>>,
+    retry: RetryPolicy,
+    session: Option<Arc<Session>>,
+}
+
+impl fmt::Debug for Client {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url)
+            .field("client", &self.client)
+            .field("cookies", &self.cookies)
+            .field("limiter", &self.limiter)
+            .field("retry", &self.retry)
+            .field("session", &self.session)
+            .finish_non_exhaustive()
+    }
 }
 
 #[non_exhaustive]
@@ -21,7 +77,7 @@ pub struct Client {
 pub struct Course {
     pub archived: bool,
     pub assessment_count: usize,
-    pub id: String,
+    pub id: CourseId,
     pub name: String,
 }
 
@@ -30,7 +86,7 @@ pub struct Course {
 pub struct Assessment {
     pub due: Option<DateTime<Utc>>,
     pub graded: Option<DateTime<Utc>>,
-    pub id: String,
+    pub id: AssessmentId,
     pub kind: AssessmentKind,
     pub score: Option<f32>,
     pub submitted: Option<DateTime<Utc>>,
@@ -38,64 +94,13 @@ pub struct Assessment {
 }
 
 #[non_exhaustive]
-#[derive(Debug, Serialize)]
+#[derive(Clone, Copy, Debug, Serialize)]
 pub enum AssessmentKind {
     Proctored,
     TakeHome,
 }
 
-#[derive(Debug, Deserialize)]
-struct ResponseRoot<DA, DR, I> {
-    data: Vec<ResponseData<DA, DR>>,
-    included: Vec<IncludedData<I>>,
-}
-
-#[derive(Debug, Deserialize)]
-struct ResponseData<A, R> {
-    #[serde(flatten)]
-    attributes: A,
-
-    id: String,
-    relationships: R,
-}
-
-#[derive(Debug, Deserialize)]
-struct IncludedData<A> {
-    #[serde(flatten)]
-    attributes: A,
-
-    id: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct RelationshipId {
-    id: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct OptionalData<T> {
-    data: Option<T>,
-}
-
-#[derive(Debug, Deserialize)]
-struct RequiredData<T> {
-    data: T,
-}
-
-#[derive(Debug, Deserialize)]
-struct EmptyStruct {}
-
 impl Client {
-    /// Gets CSRF token from Crowdmark.
-    ///
-    /// # Errors
-    ///
-    /// Returns [`CrowdmarkError`] if CSRF token not found.
-    #[inline]
-    pub async fn get_csrf(&self) -> Result<String, CrowdmarkError> {
-        get_csrf(Some(&self.client)).await
-    }
-
     /// Retrieves the list of assessments for `course_id`.
     ///
     /// # Arguments
@@ -117,119 +122,42 @@ impl Client {
     #[inline]
     pub async fn list_assessments(
         &self,
-        course_id: &str,
+        course_id: &CourseId,
     ) -> Result<Vec<Assessment>, CrowdmarkError> {
-        #[derive(Debug, Deserialize)]
-        #[serde(tag = "type", content = "attributes", rename_all_fields = "kebab-case")]
-        enum ResponseDataItem {
-            #[serde(rename = "assignments")]
-            Assignment {
-                #[serde(deserialize_with = "from_raw_normalized_points")]
-                normalized_points: Option<f32>,
-                submitted_at: Option<DateTime<Utc>>,
-                due: Option<DateTime<Utc>>,
-                marks_sent_at: Option<DateTime<Utc>>,
-            },
-        }
-
-        #[derive(Debug, Deserialize)]
-        #[serde(rename_all = "kebab-case")]
-        struct ResponseRelationship {
-            exam_master: RequiredData<RelationshipId>,
-        }
-
-        #[derive(Debug, Deserialize)]
-        enum ExamMasterKind {
-            #[serde(rename = "ExamMaster::AtHome")]
-            AtHome,
-            #[serde(rename = "ExamMaster::Proctored")]
-            Proctored,
-        }
-
-        #[derive(Debug, Deserialize)]
-        struct ExamMasterData {
-            #[serde(rename = "type")]
-            kind: ExamMasterKind,
-            title: String,
-        }
-
-        #[derive(Debug, Deserialize)]
-        #[serde(tag = "type", content = "attributes", rename_all_fields = "kebab-case")]
-        enum IncludedDataItem {
-            #[serde(rename = "exam-masters")]
-            ExamMaster(ExamMasterData),
-        }
-
         let resp = self
-            .client
-            .get("https://app.crowdmark.com/api/v2/student/assignments")
-            .query(&[
-                ("fields[exam-masters][]", "type"),
-                ("fields[exam-masters][]", "title"),
-                ("filter[course]", course_id),
-            ])
-            .send()
+            .send(|| {
+                Ok(self
+                    .client
+                    .get(self.endpoint("api/v2/student/assignments")?)
+                    .query(&[
+                        ("fields[exam-masters][]", "type"),
+                        ("fields[exam-masters][]", "title"),
+                        ("filter[course]", course_id.as_str()),
+                    ]))
+            })
             .await?;
 
-        if resp.status() == reqwest::StatusCode::FOUND {
-            return Err(CrowdmarkError::NotAuthenticated(
-                "Could not get assessments".to_owned(),
-            ));
-        }
-
-        let root: ResponseRoot<ResponseDataItem, ResponseRelationship, IncludedDataItem> =
-            resp.json().await?;
+        let document: Document<Vec<RawResource>> =
+            HttpError::check(resp, Method::GET).await?.json().await?;
 
-        let exam_masters: HashMap<_, _> = root
-            .included
+        document
+            .data::<AssignmentAttributes>()?
             .into_iter()
-            .map(|IncludedData { id, attributes }| match attributes {
-                IncludedDataItem::ExamMaster(e) => (id, e),
+            .map(|assignment| {
+                let exam_master = document
+                    .resolve::<ExamMasterAttributes>(&assignment.relationships.exam_master.data)?;
+
+                Ok(Assessment {
+                    id: AssessmentId::from_trusted(exam_master.id),
+                    title: exam_master.attributes.title,
+                    kind: exam_master.attributes.kind.into(),
+                    due: assignment.attributes.due,
+                    submitted: assignment.attributes.submitted_at,
+                    graded: assignment.attributes.marks_sent_at,
+                    score: assignment.attributes.normalized_points,
+                })
             })
-            .collect();
-
-        let assessments: Result<Vec<Assessment>, CrowdmarkError> = root
-            .data
-            .into_iter()
-            .map(
-                |ResponseData {
-                     attributes,
-                     relationships,
-                     ..
-                 }| {
-                    match attributes {
-                        ResponseDataItem::Assignment {
-                            normalized_points,
-                            submitted_at,
-                            due,
-                            marks_sent_at,
-                        } => {
-                            let exam_master = exam_masters
-                                .get(&relationships.exam_master.data.id)
-                                .ok_or(CrowdmarkError::Decode(format!(
-                                    "Missing exam_master for id {}",
-                                    relationships.exam_master.data.id
-                                )))?;
-
-                            Ok(Assessment {
-                                id: relationships.exam_master.data.id,
-                                title: exam_master.title.clone(),
-                                kind: match exam_master.kind {
-                                    ExamMasterKind::AtHome => AssessmentKind::TakeHome,
-                                    ExamMasterKind::Proctored => AssessmentKind::Proctored,
-                                },
-                                due,
-                                submitted: submitted_at,
-                                graded: marks_sent_at,
-                                score: normalized_points,
-                            })
-                        }
-                    }
-                },
-            )
-            .collect();
-
-        assessments
+            .collect()
     }
 
     /// Retrieves the list of courses available to the authenticated student.
@@ -249,57 +177,43 @@ impl Client {
     #[inline]
     pub async fn list_courses(&self) -> Result<Vec<Course>, CrowdmarkError> {
         #[derive(Debug, Deserialize)]
-        #[serde(tag = "type", content = "attributes", rename_all_fields = "kebab-case")]
-        enum ResponseDataItem {
-            #[serde(rename = "courses")]
-            Course {
-                name: String,
-                exam_master_count: usize,
-            },
+        #[serde(rename_all = "kebab-case")]
+        struct CourseAttributes {
+            name: String,
+            exam_master_count: usize,
         }
 
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "kebab-case")]
-        struct ResponseRelationship {
-            course_archivation: OptionalData<EmptyStruct>,
+        struct CourseRelationships {
+            course_archivation: Relationship<Option<Identifier>>,
+        }
+
+        impl jsonapi::Attributes for CourseAttributes {
+            const TYPE: &'static str = "courses";
+            type Relationships = CourseRelationships;
         }
 
         let resp = self
-            .client
-            .get("https://app.crowdmark.com/api/v2/student/courses?include[]=course-archivation")
-            .send()
+            .send(|| {
+                Ok(self
+                    .client
+                    .get(self.endpoint("api/v2/student/courses?include[]=course-archivation")?))
+            })
             .await?;
 
-        if resp.status() == reqwest::StatusCode::FOUND {
-            return Err(CrowdmarkError::NotAuthenticated(
-                "Could not get courses".to_owned(),
-            ));
-        }
-
-        let data = resp
-            .json::<ResponseRoot<ResponseDataItem, ResponseRelationship, EmptyStruct>>()
+        let courses = HttpError::check(resp, Method::GET)
             .await?
-            .data;
-
-        let courses: Vec<_> = data
+            .json::<Document<Vec<RawResource>>>()
+            .await?
+            .data::<CourseAttributes>()?
             .into_iter()
-            .map(
-                |ResponseData {
-                     id,
-                     attributes,
-                     relationships,
-                 }| match attributes {
-                    ResponseDataItem::Course {
-                        name,
-                        exam_master_count,
-                    } => Course {
-                        id,
-                        name,
-                        assessment_count: exam_master_count,
-                        archived: relationships.course_archivation.data.is_some(),
-                    },
-                },
-            )
+            .map(|course| Course {
+                id: CourseId::from_trusted(course.id),
+                name: course.attributes.name,
+                assessment_count: course.attributes.exam_master_count,
+                archived: course.relationships.course_archivation.data.is_some(),
+            })
             .collect();
 
         Ok(courses)
@@ -307,6 +221,9 @@ impl Client {
 
     /// Creates a new [`Client`] instance using the provided session token.
     ///
+    /// Use [`ClientBuilder`] to customize the base URL, user agent, timeout,
+    /// proxy or root certificates.
+    ///
     /// # Arguments
     ///
     /// * `session_token` - The Crowdmark session token
@@ -323,64 +240,162 @@ impl Client {
     /// `session_token` is incorrectly formatted.
     #[inline]
     pub fn new(session_token: &str) -> Result<Self, CrowdmarkError> {
-        let mut headers = header::HeaderMap::new();
-        let cookie_string = format!("cm_session_id={session_token}");
+        ClientBuilder::new().session_token(session_token).build()
+    }
 
-        let mut cookie_value = header::HeaderValue::from_str(&cookie_string)?;
-        cookie_value.set_sensitive(true);
-        headers.insert(header::COOKIE, cookie_value);
+    /// Creates a [`ClientBuilder`] to configure a [`Client`].
+    #[inline]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
 
-        let client = reqwest::Client::builder()
-            .user_agent(DEFAULT_USER_AGENT)
-            .default_headers(headers)
-            .build()?;
+    /// Downloads the file at `url`, which may be relative to the base URL.
+    /// The session is only sent along if the file is on Crowdmark.
+    async fn download(&self, url: &str) -> Result<Vec<u8>, CrowdmarkError> {
+        let url = self.endpoint(url)?;
+        let response = if url.origin() == self.base_url.origin() {
+            self.send(|| Ok(self.client.get(url.clone()))).await?
+        } else {
+            self.send_external(|| Ok(self.client.get(url.clone())))
+                .await?
+        };
+        Ok(HttpError::check(response, Method::GET)
+            .await?
+            .bytes()
+            .await?
+            .to_vec())
+    }
 
-        Ok(Self { client })
+    /// Sends the request `build` creates to Crowdmark once the rate limit
+    /// allows it, retrying it according to the client's [`RetryPolicy`]. If
+    /// the session has expired, a new one is requested and the request sent
+    /// once more.
+    async fn send<F>(&self, mut build: F) -> Result<Response, CrowdmarkError>
+    where
+        F: FnMut() -> Result<RequestBuilder, CrowdmarkError>,
+    {
+        let session = self.session_token(None).await?;
+        match (self.send_checked(&mut build).await, session) {
+            (Err(CrowdmarkError::NotAuthenticated(_)), Some(expired)) => {
+                self.session_token(Some(&expired)).await?;
+                self.send_checked(&mut build).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Sends the request `build` creates with the session in the cookie jar.
+    async fn send_checked<F>(&self, build: &mut F) -> Result<Response, CrowdmarkError>
+    where
+        F: FnMut() -> Result<RequestBuilder, CrowdmarkError>,
+    {
+        let response = self.send_external(build).await?;
+        let response = self.check_session(response)?;
+        self.session_validated();
+        Ok(response)
+    }
+
+    /// Like [`send`](Self::send), but for requests outside Crowdmark, such
+    /// as S3, whose `401`s say nothing about the session.
+    async fn send_external<F>(&self, build: F) -> Result<Response, CrowdmarkError>
+    where
+        F: FnMut() -> Result<RequestBuilder, CrowdmarkError>,
+    {
+        self.retry.send(self.limiter.as_deref(), build).await
     }
-}
 
-/// Gets CSRF token from Crowdmark.
-///
-/// # Errors
-///
-/// Returns [`CrowdmarkError`] if CSRF token not found.
-#[inline]
-pub async fn get_csrf(option_client: Option<&reqwest::Client>) -> Result<String, CrowdmarkError> {
-    let client = match option_client {
-        Some(c) => c,
-        None => &reqwest::Client::builder()
-            .user_agent(DEFAULT_USER_AGENT)
-            .build()?,
-    };
-    let resp = client
-        .get("https://app.crowdmark.com/student")
-        .send()
-        .await?;
-    let re = Regex::new(r#"<meta name="csrf-token" content="([^"]+)""#)?;
-    Ok(match re.captures(&resp.text().await?) {
-        Some(captures) => captures[1].to_string(),
-        None => {
+    /// Sends a state-changing `request` with the session and CSRF token,
+    /// without retrying. If Crowdmark rejects the CSRF token as invalid, it
+    /// is refreshed and the request sent once more; if it rejects the session,
+    /// a new session and CSRF token are requested first.
+    async fn execute(&self, request: RequestBuilder) -> Result<Response, CrowdmarkError> {
+        let session = self.session_token(None).await?;
+        let csrf = self.csrf_token(None).await?;
+        let retry = request.try_clone();
+        let result = self.send_once(request, &csrf).await;
+        match (result, retry, session) {
+            (Ok(response), Some(request), _)
+                if response.status() == StatusCode::UNPROCESSABLE_ENTITY =>
+            {
+                let (rejected, response) = rejects_csrf(response).await?;
+                if !rejected {
+                    return Ok(response);
+                }
+                let csrf = self.csrf_token(Some(&csrf)).await?;
+                self.send_once(request, &csrf).await
+            }
+            (Err(CrowdmarkError::NotAuthenticated(_)), Some(request), Some(expired)) => {
+                self.session_token(Some(&expired)).await?;
+                let csrf = self.csrf_token(Some(&csrf)).await?;
+                self.send_once(request, &csrf).await
+            }
+            (result, _, _) => result,
+        }
+    }
+
+    /// Sends `request` to Crowdmark with the `csrf` token once the rate
+    /// limit allows it.
+    async fn send_once(
+        &self,
+        request: RequestBuilder,
+        csrf: &str,
+    ) -> Result<Response, CrowdmarkError> {
+        let request = request.header(CSRF_HEADER, csrf);
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire().await;
+        }
+        let response = self.check_session(request.send().await?)?;
+        self.session_validated();
+        Ok(response)
+    }
+
+    /// Turns a response showing that the session is missing or has expired
+    /// into [`CrowdmarkError::NotAuthenticated`]. Crowdmark redirects pages
+    /// to the sign-in form, which the client follows, and answers API
+    /// requests with `401`. A `403` only means the session may not do this,
+    /// so it is left for [`HttpError::check`] to report.
+    fn check_session(&self, response: Response) -> Result<Response, CrowdmarkError> {
+        let mut url = response.url().clone();
+        url.set_query(None);
+        if url == self.endpoint("sign-in")? {
             return Err(CrowdmarkError::NotAuthenticated(
-                "Missing CSRF Token".to_owned(),
+                "Redirected to sign-in".to_owned(),
             ));
         }
-    })
-}
+        match response.status() {
+            status @ StatusCode::UNAUTHORIZED => Err(CrowdmarkError::NotAuthenticated(format!(
+                "{} returned {status}",
+                url.path()
+            ))),
+            _ => Ok(response),
+        }
+    }
 
-fn from_raw_normalized_points<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    #[derive(Deserialize)]
-    #[serde(untagged)]
-    enum RawNormalizedPoints {
-        Str(String),
-        #[expect(dead_code)]
-        Zero(usize),
+    /// Resolves `path` against the client's base URL.
+    fn endpoint(&self, path: &str) -> Result<Url, CrowdmarkError> {
+        Ok(self.base_url.join(path)?)
     }
+}
 
-    match RawNormalizedPoints::deserialize(deserializer)? {
-        RawNormalizedPoints::Zero(_) => Ok(None),
-        RawNormalizedPoints::Str(s) => s.parse::<f32>().map(Some).map_err(serde::de::Error::custom),
+/// Tells whether a `422` response is Crowdmark rejecting the CSRF token
+/// rather than the request itself. The body has to be read to tell, so the
+/// response is handed back rebuilt around it.
+async fn rejects_csrf(response: Response) -> Result<(bool, Response), CrowdmarkError> {
+    let mut rebuilt = http::Response::builder()
+        .status(response.status())
+        .version(response.version())
+        .url(response.url().clone());
+    if let Some(headers) = rebuilt.headers_mut() {
+        headers.extend(response.headers().clone());
     }
+    let body = response.bytes().await?;
+    // Matches both a message such as "Invalid authenticity token" and an
+    // error code such as `InvalidAuthenticityToken`.
+    let rejected = String::from_utf8_lossy(&body)
+        .to_ascii_lowercase()
+        .contains("authenticity");
+    let response = rebuilt
+        .body(body)
+        .map_err(|err| CrowdmarkError::Decode(err.to_string()))?;
+    Ok((rejected, Response::from(response)))
 }
@@ -1,6 +1,8 @@
-use crate::error::CrowdmarkError;
+use crate::ClientBuilder;
+use crate::error::{CrowdmarkError, HttpError};
+use chrono::{DateTime, TimeDelta, Utc};
 use regex_lite::Regex;
-use reqwest::Client;
+use reqwest::Method;
 
 /// Logs in to Crowdmark.
 ///
@@ -9,34 +11,64 @@ use reqwest::Client;
 /// Returns [`CrowdmarkError`] if the request to Crowdmark fails.
 #[inline]
 pub async fn get_token(email: String, password: String) -> Result<String, CrowdmarkError> {
-    let client = Client::builder().cookie_store(true).build()?;
-    let resp = client
-        .get("https://app.crowdmark.com/sign-in")
-        .send()
-        .await?;
+    ClientBuilder::new().login(email, password).await
+}
+
+impl ClientBuilder {
+    /// Logs in to the configured Crowdmark instance and returns the session
+    /// token.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the request to Crowdmark fails.
+    #[inline]
+    pub async fn login(&self, email: String, password: String) -> Result<String, CrowdmarkError> {
+        Ok(self.sign_in(email, password).await?.0)
+    }
+
+    /// Logs in like [`login`](Self::login), also returning when the session
+    /// cookie expires, if Crowdmark says.
+    pub(crate) async fn sign_in(
+        &self,
+        email: String,
+        password: String,
+    ) -> Result<(String, Option<DateTime<Utc>>), CrowdmarkError> {
+        let client = self.http_client().cookie_store(true).build()?;
+        let sign_in_url = self.parsed_base_url()?.join("sign-in")?;
+        let limiter = self.limiter();
+        let resp = self
+            .retry_policy
+            .send(limiter.as_deref(), || Ok(client.get(sign_in_url.clone())))
+            .await?;
 
-    let re = Regex::new(r#"name="authenticity_token" value="([^"]+)""#)?;
-    let authenticity_token = re
-        .captures(&resp.text().await?)
-        .map(|capture| capture[1].to_string())
-        .ok_or_else(|| CrowdmarkError::NotAuthenticated("Missing authenticity token".into()))?;
-    let params = [
-        ("authenticity_token", authenticity_token),
-        ("user[email]", email.to_owned()),
-        ("user[password]", password.to_owned()),
-        ("commit", "Sign+in".to_owned()),
-    ];
+        let re = Regex::new(r#"name="authenticity_token" value="([^"]+)""#)?;
+        let authenticity_token = re
+            .captures(&HttpError::check(resp, Method::GET).await?.text().await?)
+            .map(|capture| capture[1].to_string())
+            .ok_or_else(|| CrowdmarkError::NotAuthenticated("Missing authenticity token".into()))?;
+        let params = [
+            ("authenticity_token", authenticity_token),
+            ("user[email]", email.to_owned()),
+            ("user[password]", password.to_owned()),
+            ("commit", "Sign+in".to_owned()),
+        ];
 
-    let login_resp = client
-        .post("https://app.crowdmark.com/sign-in")
-        .form(&params)
-        .send()
-        .await?;
+        if let Some(limiter) = &limiter {
+            limiter.acquire().await;
+        }
+        let login_resp = client.post(sign_in_url).form(&params).send().await?;
 
-    Ok(login_resp
-        .cookies()
-        .find(|cookie| cookie.name() == "cm_session_id")
-        .ok_or(CrowdmarkError::Login())?
-        .value()
-        .to_owned())
+        let cookie = login_resp
+            .cookies()
+            .find(|cookie| cookie.name() == "cm_session_id")
+            .ok_or(CrowdmarkError::Login())?;
+        let expires = match (cookie.max_age(), cookie.expires()) {
+            (Some(max_age), _) => TimeDelta::from_std(max_age)
+                .ok()
+                .and_then(|max_age| Utc::now().checked_add_signed(max_age)),
+            (None, Some(expires)) => Some(expires.into()),
+            (None, None) => None,
+        };
+        Ok((cookie.value().to_owned(), expires))
+    }
 }
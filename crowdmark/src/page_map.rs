@@ -0,0 +1,122 @@
+//! Assignment of uploaded pages to questions.
+//!
+//! A [`PageMap`] is written as comma-separated `question=pages` entries,
+//! where `pages` is a single page or an inclusive range, e.g.
+//! `1=1-2,2=3,3=4-6`. Questions and pages are both numbered from 1.
+
+use crate::error::CrowdmarkError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Maps question sequence numbers to the pages that answer them.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct PageMap(BTreeMap<usize, Vec<usize>>);
+
+impl PageMap {
+    /// Creates an empty mapping.
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maps page `n` to question `n` for the first `page_count` pages.
+    #[inline]
+    #[must_use]
+    pub fn one_per_question(page_count: usize) -> Self {
+        Self((1..=page_count).map(|n| (n, vec![n])).collect())
+    }
+
+    /// Appends `pages` to the pages answering `question`.
+    #[inline]
+    pub fn insert<I>(&mut self, question: usize, pages: I) -> &mut Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        self.0.entry(question).or_default().extend(pages);
+        self
+    }
+
+    /// Returns the pages answering `question`, if any.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, question: usize) -> Option<&[usize]> {
+        self.0.get(&question).map(Vec::as_slice)
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over questions in sequence order along with their pages.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[usize])> {
+        self.0
+            .iter()
+            .map(|(&question, pages)| (question, pages.as_slice()))
+    }
+}
+
+impl FromStr for PageMap {
+    type Err = CrowdmarkError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |entry: &str| CrowdmarkError::InvalidPageMap(entry.trim().to_owned());
+        let number = |n: &str, entry: &str| match n.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(invalid(entry)),
+        };
+
+        let mut map = Self::new();
+        for entry in s.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (question, pages) = entry.split_once('=').ok_or_else(|| invalid(entry))?;
+            let question = number(question, entry)?;
+            let (first, last) = match pages.split_once('-') {
+                Some((first, last)) => (number(first, entry)?, number(last, entry)?),
+                None => {
+                    let page = number(pages, entry)?;
+                    (page, page)
+                }
+            };
+            if first > last {
+                return Err(invalid(entry));
+            }
+            map.insert(question, first..=last);
+        }
+
+        if map.is_empty() {
+            return Err(invalid(s));
+        }
+        Ok(map)
+    }
+}
+
+impl fmt::Display for PageMap {
+    /// Formats the mapping in the same syntax [`PageMap::from_str`] accepts,
+    /// collapsing consecutive pages into ranges.
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        for (question, pages) in self.iter() {
+            let mut pages = pages.iter().copied().peekable();
+            while let Some(first) = pages.next() {
+                let mut last = first;
+                while pages.next_if_eq(&(last + 1)).is_some() {
+                    last += 1;
+                }
+                if first == last {
+                    write!(f, "{separator}{question}={first}")?;
+                } else {
+                    write!(f, "{separator}{question}={first}-{last}")?;
+                }
+                separator = ",";
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use crate::ids::{PageId, QuestionId};
+use serde::Serialize;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Bytes handed to the S3 request at a time, and so the granularity of
+/// [`UploadEvent::UploadProgress`].
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Progress of an upload, sent to the channel given to
+/// [`UploadOptions::progress`](crate::UploadOptions::progress). Pages are
+/// numbered as in the uploaded PDF.
+///
+/// The library does not render or submit anything while uploading, so the
+/// render and submission events are for callers that do those steps to
+/// report through the same channel.
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "event")]
+pub enum UploadEvent {
+    /// The page started rendering.
+    RenderStarted { page: usize },
+    /// The page finished rendering.
+    RenderFinished { page: usize },
+    /// The page's image started uploading to S3.
+    UploadStarted { page: usize, total: u64 },
+    /// `sent` of the image's `total` bytes were handed to S3.
+    UploadProgress { page: usize, sent: u64, total: u64 },
+    /// The page's image finished uploading to S3.
+    UploadFinished { page: usize },
+    /// The page was attached to a question of the draft. Also sent for pages
+    /// kept from an earlier journaled attempt.
+    PageRegistered {
+        page: usize,
+        page_id: PageId,
+        question_id: QuestionId,
+    },
+    /// The assessment was submitted.
+    Submitted,
+}
+
+/// Sends `event` if anyone is listening.
+pub(crate) fn report(progress: Option<&UnboundedSender<UploadEvent>>, event: UploadEvent) {
+    if let Some(progress) = progress {
+        // The receiver may stop listening at any time.
+        let _sent = progress.send(event);
+    }
+}
+
+/// Request body that yields an image in chunks, reporting each one as it is
+/// taken.
+pub(crate) struct ProgressBody {
+    img: Vec<u8>,
+    page: usize,
+    progress: Option<UnboundedSender<UploadEvent>>,
+    sent: usize,
+}
+
+impl ProgressBody {
+    pub(crate) fn new(
+        img: Vec<u8>,
+        page: usize,
+        progress: Option<UnboundedSender<UploadEvent>>,
+    ) -> Self {
+        Self {
+            img,
+            page,
+            progress,
+            sent: 0,
+        }
+    }
+}
+
+impl futures_core::Stream for ProgressBody {
+    type Item = Result<Vec<u8>, std::io::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let body = self.get_mut();
+        if body.sent == body.img.len() {
+            return Poll::Ready(None);
+        }
+        let end = body.img.len().min(body.sent + CHUNK_SIZE);
+        let chunk = body.img[body.sent..end].to_vec();
+        body.sent = end;
+        report(
+            body.progress.as_ref(),
+            UploadEvent::UploadProgress {
+                page: body.page,
+                sent: body.sent as u64,
+                total: body.img.len() as u64,
+            },
+        );
+        Poll::Ready(Some(Ok(chunk)))
+    }
+}
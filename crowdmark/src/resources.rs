@@ -0,0 +1,132 @@
+//! JSON:API resources returned by the Crowdmark student API.
+
+use crate::AssessmentKind;
+use crate::jsonapi::{Attributes, Empty, Identifier, Relationship};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct AssignmentAttributes {
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub late_due: Option<DateTime<Utc>>,
+    pub marks_sent_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "from_raw_normalized_points")]
+    pub normalized_points: Option<f32>,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct AssignmentRelationships {
+    pub exam_master: Relationship<Identifier>,
+}
+
+impl Attributes for AssignmentAttributes {
+    const TYPE: &'static str = "assignments";
+    type Relationships = AssignmentRelationships;
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub(crate) enum ExamMasterKind {
+    #[serde(rename = "ExamMaster::AtHome")]
+    AtHome,
+    #[serde(rename = "ExamMaster::Proctored")]
+    Proctored,
+}
+
+impl From<ExamMasterKind> for AssessmentKind {
+    #[inline]
+    fn from(kind: ExamMasterKind) -> Self {
+        match kind {
+            ExamMasterKind::AtHome => Self::TakeHome,
+            ExamMasterKind::Proctored => Self::Proctored,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct ExamMasterAttributes {
+    #[serde(rename = "type")]
+    pub kind: ExamMasterKind,
+    pub title: String,
+}
+
+impl Attributes for ExamMasterAttributes {
+    const TYPE: &'static str = "exam-masters";
+    type Relationships = Empty;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct CommentAttributes {
+    pub body: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct CommentRelationships {
+    pub question: Relationship<Identifier>,
+}
+
+impl Attributes for CommentAttributes {
+    const TYPE: &'static str = "comments";
+    type Relationships = CommentRelationships;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct PageAttributes {
+    pub filename: Option<String>,
+    pub marked_url: Option<String>,
+    pub number: Option<i64>,
+    pub url: Option<String>,
+    pub uuid: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct PageRelationships {
+    pub question: Option<Relationship<Option<Identifier>>>,
+}
+
+impl Attributes for PageAttributes {
+    const TYPE: &'static str = "assignment-pages";
+    type Relationships = PageRelationships;
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub(crate) struct QuestionAttributes {
+    pub label: Option<String>,
+    pub points: Option<f32>,
+    pub score: Option<f32>,
+    pub sequence: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct QuestionRelationships {
+    #[serde(default)]
+    pub anchored_to_exam_page: Option<Relationship<Option<Identifier>>>,
+}
+
+impl Attributes for QuestionAttributes {
+    const TYPE: &'static str = "assignment-questions";
+    type Relationships = QuestionRelationships;
+}
+
+fn from_raw_normalized_points<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawNormalizedPoints {
+        Str(String),
+        #[expect(dead_code)]
+        Zero(usize),
+    }
+
+    match RawNormalizedPoints::deserialize(deserializer)? {
+        RawNormalizedPoints::Zero(_) => Ok(None),
+        RawNormalizedPoints::Str(s) => s.parse::<f32>().map(Some).map_err(serde::de::Error::custom),
+    }
+}
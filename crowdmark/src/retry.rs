@@ -0,0 +1,149 @@
+use crate::error::{CrowdmarkError, ErrorKind};
+use crate::rate_limit::RateLimiter;
+use chrono::{DateTime, Utc};
+use reqwest::{RequestBuilder, Response, header};
+use std::time::Duration;
+
+/// How requests that fail for a transient reason are retried.
+///
+/// Only requests that are safe to repeat are retried: `GET`s, S3 policy
+/// requests, S3 uploads, which reuse the same key, and page registrations,
+/// which are first checked for having gone through. A request is retried
+/// after a connection error, a timeout, or a response classified as
+/// [`ErrorKind::Retryable`], waiting for an exponentially growing, jittered delay or for as
+/// long as the response's `Retry-After` header asks.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct RetryPolicy {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_retries: u32,
+}
+
+impl Default for RetryPolicy {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retries: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy: up to 3 retries, starting at 500 ms and
+    /// waiting at most 30 s.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy that never retries.
+    #[inline]
+    pub fn none() -> Self {
+        Self::default().max_retries(0)
+    }
+
+    /// Sets the delay before the first retry, which doubles with every
+    /// further one.
+    #[inline]
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    /// Sets the longest delay between attempts. A response whose
+    /// `Retry-After` asks for longer is not retried.
+    #[inline]
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Sets how many times a request is retried after the first attempt.
+    #[inline]
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sends the request `build` creates, building and sending it again
+    /// after each transient failure. Requests are rebuilt because streamed
+    /// bodies cannot be cloned. Every attempt waits for `limiter`, if given.
+    pub(crate) async fn send<F>(
+        &self,
+        limiter: Option<&RateLimiter>,
+        mut build: F,
+    ) -> Result<Response, CrowdmarkError>
+    where
+        F: FnMut() -> Result<RequestBuilder, CrowdmarkError>,
+    {
+        let mut attempt = 0;
+        loop {
+            let request = build()?;
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+            let result = request.send().await.map_err(CrowdmarkError::from);
+            let Some(delay) = self.delay(attempt, &result) else {
+                return result;
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait before retrying after `result`, or `None` if it
+    /// should not be retried.
+    pub(crate) fn delay(
+        &self,
+        attempt: u32,
+        result: &Result<Response, CrowdmarkError>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        let retry_after = match result {
+            Ok(response) if ErrorKind::of(response.status()) == ErrorKind::Retryable => {
+                retry_after(response)
+            }
+            Err(CrowdmarkError::Reqwest(err))
+                if err.is_connect() || err.is_timeout() || err.is_request() =>
+            {
+                None
+            }
+            _ => return None,
+        };
+        match retry_after {
+            Some(delay) => (delay <= self.max_backoff).then_some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Exponential backoff for `attempt`, jittered to between half and all
+    /// of it so that concurrent uploads do not retry in lockstep.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        delay / 2 + delay.mul_f64(fastrand::f64()) / 2
+    }
+}
+
+/// Parses a `Retry-After` header given in seconds or as an HTTP date.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.with_timezone(&Utc)
+            .signed_duration_since(Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
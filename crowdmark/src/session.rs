@@ -0,0 +1,364 @@
+use crate::ClientBuilder;
+use crate::error::CrowdmarkError;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Future returned by [`SessionProvider::session_token`].
+pub type SessionFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<String, CrowdmarkError>> + Send + 'a>>;
+
+/// Source of the `cm_session_id` a [`Client`](crate::Client) authenticates
+/// with.
+///
+/// The client asks for a token before its first request and again whenever
+/// Crowdmark rejects the current one, after which the failed request is sent
+/// once more.
+pub trait SessionProvider: fmt::Debug + Send + Sync {
+    /// Returns a session token for the Crowdmark instance `builder` is
+    /// configured for. `expired` is the token Crowdmark has just rejected,
+    /// if any; a provider that cannot replace it returns an error.
+    fn session_token<'a>(
+        &'a self,
+        builder: &'a ClientBuilder,
+        expired: Option<&'a str>,
+    ) -> SessionFuture<'a>;
+
+    /// Called once Crowdmark has accepted `token`, the first time for each
+    /// token, including those Crowdmark rotates in with `Set-Cookie`.
+    /// `expires` is when its cookie expires, unless it lasts for the
+    /// session. It is called while a request is being handled, so slow
+    /// work such as I/O belongs on another thread. Does nothing by default.
+    #[inline]
+    fn session_validated(&self, token: &str, expires: Option<DateTime<Utc>>) {
+        let _ = (token, expires);
+    }
+}
+
+/// Provides a fixed session token, such as one exported by `climark login`.
+#[derive(Clone)]
+pub struct StaticSession {
+    token: String,
+}
+
+impl StaticSession {
+    /// Creates a provider that always returns `token`.
+    #[inline]
+    #[must_use]
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_owned(),
+        }
+    }
+}
+
+impl fmt::Debug for StaticSession {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticSession").finish_non_exhaustive()
+    }
+}
+
+impl SessionProvider for StaticSession {
+    #[inline]
+    fn session_token<'a>(
+        &'a self,
+        _builder: &'a ClientBuilder,
+        expired: Option<&'a str>,
+    ) -> SessionFuture<'a> {
+        Box::pin(async move {
+            if expired == Some(self.token.as_str()) {
+                return Err(CrowdmarkError::NotAuthenticated(
+                    "Session token was rejected".to_owned(),
+                ));
+            }
+            Ok(self.token.clone())
+        })
+    }
+}
+
+/// Logs in with an email and password, and again whenever the session
+/// expires.
+#[derive(Clone)]
+pub struct PasswordSession {
+    email: String,
+    password: String,
+}
+
+impl PasswordSession {
+    /// Creates a provider that logs in as `email`.
+    #[inline]
+    #[must_use]
+    pub const fn new(email: String, password: String) -> Self {
+        Self { email, password }
+    }
+}
+
+impl fmt::Debug for PasswordSession {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PasswordSession")
+            .field("email", &self.email)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SessionProvider for PasswordSession {
+    #[inline]
+    fn session_token<'a>(
+        &'a self,
+        builder: &'a ClientBuilder,
+        _expired: Option<&'a str>,
+    ) -> SessionFuture<'a> {
+        Box::pin(builder.login(self.email.clone(), self.password.clone()))
+    }
+}
+
+/// Logs in with an email and password kept in the system keyring as JSON,
+/// and again whenever the session expires.
+///
+/// The session token is cached in a second keyring entry, under the service
+/// name with `.session` appended, and reused until Crowdmark rejects it or
+/// its cookie expires.
+///
+/// Requires the `keyring` feature, along with one of the `keyring` crate's
+/// platform store features.
+#[cfg(feature = "keyring")]
+#[derive(Debug)]
+pub struct KeyringSession {
+    entry: keyring::Entry,
+    /// Shared with the blocking tasks that cache validated tokens.
+    session: Arc<keyring::Entry>,
+}
+
+#[cfg(feature = "keyring")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct Credentials {
+    email: String,
+    password: String,
+}
+
+/// A session token cached by [`KeyringSession`].
+#[cfg(feature = "keyring")]
+#[derive(serde::Deserialize, serde::Serialize)]
+struct CachedSession {
+    /// When the session cookie expires, if Crowdmark said.
+    expires: Option<DateTime<Utc>>,
+    token: String,
+    /// When Crowdmark last accepted the token.
+    validated: DateTime<Utc>,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringSession {
+    /// Creates a provider reading the keyring entry for `service` and
+    /// `user`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the keyring entry cannot be created.
+    #[inline]
+    pub fn new(service: &str, user: &str) -> Result<Self, CrowdmarkError> {
+        Ok(Self {
+            entry: keyring::Entry::new(service, user)?,
+            session: Arc::new(keyring::Entry::new(&format!("{service}.session"), user)?),
+        })
+    }
+
+    /// Returns whether credentials have been stored yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the keyring cannot be read.
+    #[inline]
+    pub fn has_credentials(&self) -> Result<bool, CrowdmarkError> {
+        match self.entry.get_password() {
+            Ok(_) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Stores the credentials later logins use.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`CrowdmarkError`] if the keyring cannot be written.
+    #[inline]
+    pub fn set_credentials(&self, email: &str, password: &str) -> Result<(), CrowdmarkError> {
+        let credentials = Credentials {
+            email: email.to_owned(),
+            password: password.to_owned(),
+        };
+        Ok(self
+            .entry
+            .set_password(&serde_json::to_string(&credentials)?)?)
+    }
+
+    /// Returns the cached session, unless there is none or it cannot be
+    /// read, in which case logging in again replaces it.
+    fn cached_session(&self) -> Option<CachedSession> {
+        CachedSession::read(&self.session)
+    }
+
+    fn cache_session(&self, session: &CachedSession) -> Result<(), CrowdmarkError> {
+        session.write(&self.session)
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl CachedSession {
+    fn read(entry: &keyring::Entry) -> Option<Self> {
+        serde_json::from_str(&entry.get_password().ok()?).ok()
+    }
+
+    fn write(&self, entry: &keyring::Entry) -> Result<(), CrowdmarkError> {
+        Ok(entry.set_password(&serde_json::to_string(self)?)?)
+    }
+
+    /// Records that Crowdmark accepted `token` in `entry`.
+    fn validate(entry: &keyring::Entry, token: String, expires: Option<DateTime<Utc>>) {
+        let cached = match Self::read(entry) {
+            // The cookie that came with a token from the cache carries no
+            // expiry, so keep the one stored with it.
+            Some(cached) if cached.token == token => Self {
+                validated: Utc::now(),
+                ..cached
+            },
+            _ => Self {
+                expires,
+                token,
+                validated: Utc::now(),
+            },
+        };
+        // A token that is not cached is asked for again on the next run, so
+        // failing to store it is not worth failing the request over.
+        cached.write(entry).ok();
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl SessionProvider for KeyringSession {
+    #[inline]
+    fn session_token<'a>(
+        &'a self,
+        builder: &'a ClientBuilder,
+        expired: Option<&'a str>,
+    ) -> SessionFuture<'a> {
+        Box::pin(async move {
+            if let Some(cached) = self.cached_session()
+                && Some(cached.token.as_str()) != expired
+                && cached.expires.is_none_or(|expires| expires > Utc::now())
+            {
+                return Ok(cached.token);
+            }
+
+            let credentials: Credentials = serde_json::from_str(&self.entry.get_password()?)?;
+            let (token, expires) = builder
+                .sign_in(credentials.email, credentials.password)
+                .await?;
+            self.cache_session(&CachedSession {
+                expires,
+                token: token.clone(),
+                validated: Utc::now(),
+            })?;
+            Ok(token)
+        })
+    }
+
+    #[inline]
+    fn session_validated(&self, token: &str, expires: Option<DateTime<Utc>>) {
+        let token = token.to_owned();
+        // The keyring blocks, so keep it off the runtime's worker threads.
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                let session = Arc::<keyring::Entry>::clone(&self.session);
+                drop(runtime.spawn_blocking(move || {
+                    CachedSession::validate(&session, token, expires);
+                }));
+            }
+            Err(_) => CachedSession::validate(&self.session, token, expires),
+        }
+    }
+}
+
+/// The session shared by every clone of a [`Client`](crate::Client).
+pub(crate) struct Session {
+    /// Configuration the provider logs in with.
+    pub(crate) builder: ClientBuilder,
+    /// Held while asking the provider for a token.
+    pub(crate) login: tokio::sync::Mutex<()>,
+    pub(crate) provider: Arc<dyn SessionProvider>,
+    /// The token the provider was last told Crowdmark accepted.
+    pub(crate) validated: Mutex<Option<String>>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("builder", &self.builder)
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
+impl crate::Client {
+    /// Returns the session token in the cookie jar, asking the provider for
+    /// one first if there is none yet or if the current one is `expired`,
+    /// i.e. Crowdmark has just rejected it. A client without a provider can
+    /// only use the token it has, if any.
+    pub(crate) async fn session_token(
+        &self,
+        expired: Option<&str>,
+    ) -> Result<Option<String>, CrowdmarkError> {
+        let Some(session) = &self.session else {
+            return match self.cookies.session_token(&self.base_url) {
+                Some(token) if Some(token.as_str()) == expired => Err(
+                    CrowdmarkError::NotAuthenticated("Session token was rejected".to_owned()),
+                ),
+                token => Ok(token),
+            };
+        };
+        // As with the CSRF token, holding the lock makes concurrent callers
+        // wait for one login instead of each starting their own.
+        let _login = session.login.lock().await;
+        if let Some(token) = self.cookies.session_token(&self.base_url)
+            && Some(token.as_str()) != expired
+        {
+            return Ok(Some(token));
+        }
+
+        let token = session
+            .provider
+            .session_token(&session.builder, expired)
+            .await?;
+        self.cookies.set_session_token(&self.base_url, &token)?;
+        Ok(Some(token))
+    }
+
+    /// Tells the provider that Crowdmark accepted the session token now in
+    /// the cookie jar, unless it already knows. After a successful response
+    /// that is either the token sent or one Crowdmark rotated in with it.
+    pub(crate) fn session_validated(&self) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let Some((token, expires)) = self.cookies.session(&self.base_url) else {
+            return;
+        };
+        {
+            let mut validated = session
+                .validated
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if validated.as_deref() == Some(token.as_str()) {
+                return;
+            }
+            *validated = Some(token.clone());
+        }
+        // Told without holding the lock, as providers may be slow to react.
+        session.provider.session_validated(&token, expires);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::DraftPage;
+use crate::error::CrowdmarkError;
+use crate::ids::{AssessmentId, PageId, QuestionId};
+use serde::Serialize;
+
+/// The page images Crowdmark holds for an assessment, grouped by question.
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct Submission {
+    pub assessment_id: AssessmentId,
+    /// Questions in sequence order.
+    pub questions: Vec<SubmittedQuestion>,
+    /// Uploaded pages not mapped to any question, in page order.
+    pub unassigned_pages: Vec<SubmittedPage>,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct SubmittedQuestion {
+    pub id: QuestionId,
+    pub label: String,
+    /// Uploaded pages for this question, in page order.
+    pub pages: Vec<SubmittedPage>,
+    pub sequence: usize,
+}
+
+#[non_exhaustive]
+#[derive(Clone, Debug, Serialize)]
+pub struct SubmittedPage {
+    pub id: PageId,
+    #[serde(skip)]
+    pub image: Vec<u8>,
+    pub number: i64,
+}
+
+impl crate::Client {
+    /// Downloads the page images currently uploaded for an assessment.
+    ///
+    /// # Arguments
+    ///
+    /// * `assessment_id` - The assessment to download.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a [`CrowdmarkError`] if:
+    /// * The request to the Crowdmark API fails.
+    /// * A page has no image URL or its image cannot be downloaded.
+    #[inline]
+    pub async fn download_submission(
+        &self,
+        assessment_id: &AssessmentId,
+    ) -> Result<Submission, CrowdmarkError> {
+        let detail = self.get_assessment(assessment_id).await?;
+
+        let mut questions = Vec::with_capacity(detail.questions.len());
+        for question in detail.questions {
+            questions.push(SubmittedQuestion {
+                id: question.id,
+                label: question.label,
+                pages: self.download_pages(question.pages).await?,
+                sequence: question.sequence,
+            });
+        }
+        let mut unassigned_pages = detail.unassigned_pages;
+        unassigned_pages.sort_by_key(|page| page.number);
+
+        Ok(Submission {
+            assessment_id: detail.id,
+            questions,
+            unassigned_pages: self.download_pages(unassigned_pages).await?,
+        })
+    }
+
+    /// Downloads the image of each of `pages`.
+    async fn download_pages(
+        &self,
+        pages: Vec<DraftPage>,
+    ) -> Result<Vec<SubmittedPage>, CrowdmarkError> {
+        let mut submitted = Vec::with_capacity(pages.len());
+        for page in pages {
+            let url = page.url.ok_or_else(|| {
+                CrowdmarkError::Decode(format!("Missing image URL for page {}", page.id))
+            })?;
+            submitted.push(SubmittedPage {
+                id: page.id,
+                image: self.download(&url).await?,
+                number: page.number,
+            });
+        }
+        Ok(submitted)
+    }
+}
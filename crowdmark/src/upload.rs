@@ -1,149 +1,112 @@
-use crate::error::CrowdmarkError;
-use reqwest::multipart;
+use crate::assessment::{AssessmentDetail, DraftPage, Question};
+use crate::error::{CrowdmarkError, HttpError};
+use crate::ids::{AssessmentId, AssignmentId, PageId, QuestionId};
+use crate::journal::{self, Journal, JournalEntry};
+use crate::jsonapi::{Document, RawResource};
+use crate::page_map::PageMap;
+use crate::progress::{self, ProgressBody, UploadEvent};
+use crate::resources::PageAttributes;
+use reqwest::{Body, Method, multipart};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-
-#[derive(Clone, Debug, Deserialize)]
-struct AssessResponse {
-    data: ResponseData,
-    included: Vec<IncludedItem>,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct ResponseData {
-    id: String,
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+/// Pages uploaded at once unless [`UploadOptions::jobs`] says otherwise.
+const DEFAULT_JOBS: usize = 4;
+
+/// What happens to an assessment's existing draft when uploading.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UploadMode {
+    /// Replace every existing draft page.
+    #[default]
+    Replace,
+    /// Replace only the pages of questions being uploaded, keeping the rest
+    /// of the draft.
+    ReplaceQuestions,
+    /// Keep every existing draft page and add the new pages after them.
+    Append,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct IncludedItem {
-    #[serde(default)]
-    attributes: IncludedAttributes,
-    id: String,
-    #[serde(default)]
-    relationships: Option<IncludedRelationships>,
-    #[serde(rename = "type")]
-    type_: String,
+/// Options for [`Client::upload_mapped_assessment`](crate::Client::upload_mapped_assessment).
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct UploadOptions {
+    jobs: usize,
+    journal: Option<PathBuf>,
+    mode: UploadMode,
+    progress: Option<UnboundedSender<UploadEvent>>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
-struct IncludedAttributes {
-    filename: Option<String>,
-    number: Option<i64>,
-    sequence: Option<usize>,
-    uuid: Option<String>,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct IncludedRelationships {
-    question: Option<SingleRelationship>,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct SingleRelationship {
-    data: Option<RelationData>,
-}
-
-#[derive(Clone, Debug, Deserialize)]
-struct RelationData {
-    id: Value,
+impl Default for UploadOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            jobs: DEFAULT_JOBS,
+            journal: None,
+            mode: UploadMode::default(),
+            progress: None,
+        }
+    }
 }
 
-impl crate::Client {
-    async fn clear_pages(
-        &self,
-        csrf: &str,
-        shared_root: Arc<AssessResponse>,
-    ) -> Result<(), CrowdmarkError> {
-        let csrf_token = csrf.to_owned();
-
-        for item in shared_root.included.iter() {
-            let client = self.client.clone();
-            let cloned_item = item.clone();
-            let token = csrf_token.clone();
-            let root_ref = Arc::<AssessResponse>::clone(&shared_root);
-
-            if cloned_item.type_ == "assignment-pages" {
-                let body = serde_json::json!({
-                    "data": {
-                        "id": cloned_item.id,
-                        "type": "assignment-pages",
-                        "attributes": { "state": "pending_delete" },
-                        "relationships": {
-                            "question": { "data": { "type": "assignment-questions", "id": "" } }
-                        }
-                    }
-                });
+impl UploadOptions {
+    /// Creates options that replace the whole draft without a journal.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-                client
-                    .patch(format!(
-                        "https://app.crowdmark.com/api/v2/student/assignment-pages/{}",
-                        cloned_item.id
-                    ))
-                    .header("Content-Type", "application/vnd.api+json")
-                    .header("X-Csrf-Token", token)
-                    .json(&body)
-                    .send()
-                    .await?
-                    .error_for_status()?;
-            } else if cloned_item.type_ == "assignment-questions" {
-                let body = serde_json::json!({
-                    "data": {
-                        "id": cloned_item.id,
-                        "type": "assignment-questions",
-                        "relationships": {
-                            "anchored-to-exam-page": { "data": serde_json::Value::Null },
-                            "assignment": { "data": { "id": root_ref.data.id, "type": "assignments" } }
-                        }
-                    }
-                });
+    /// Sets how many pages are uploaded at once. Defaults to 4.
+    #[inline]
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
 
-                client
-                    .patch(format!(
-                        "https://app.crowdmark.com/api/v2/student/assignment-questions/{}",
-                        cloned_item.id
-                    ))
-                    .header("Content-Type", "application/vnd.api+json")
-                    .header("X-Csrf-Token", token)
-                    .json(&body)
-                    .send()
-                    .await?
-                    .error_for_status()?;
-            };
-        }
+    /// Records the images sent to S3 and the assignment pages created from
+    /// them in a journal at `path`, so that re-running a failed or
+    /// interrupted upload with the same journal only uploads the remaining
+    /// images and keeps the pages that are still in the draft. The journal is
+    /// deleted once the upload completes.
+    #[inline]
+    pub fn journal(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal = Some(path.into());
+        self
+    }
 
-        Ok(())
+    /// Sets which existing draft pages are kept. Defaults to
+    /// [`UploadMode::Replace`].
+    #[inline]
+    pub fn mode(mut self, mode: UploadMode) -> Self {
+        self.mode = mode;
+        self
     }
 
-    async fn fetch_assessment(
-        &self,
-        assessment_id: &str,
-    ) -> Result<AssessResponse, CrowdmarkError> {
-        let resp = self
-            .client
-            .get(format!(
-                    "https://app.crowdmark.com/api/v2/student/assignments/{assessment_id}?fields[exam-masters][]=type&fields[exam-masters][]=title",
-            ))
-            .send()
-            .await?;
-        let text = resp.text().await?;
-        Ok(serde_json::from_str::<AssessResponse>(&text)?)
+    /// Sends an [`UploadEvent`] to `progress` as each page moves through the
+    /// upload.
+    #[inline]
+    pub fn progress(mut self, progress: UnboundedSender<UploadEvent>) -> Self {
+        self.progress = Some(progress);
+        self
     }
+}
 
-    /// Starts drafting an assessment.
+impl crate::Client {
+    /// Starts drafting an assignment.
     ///
     /// # Errors
     ///
     /// Returns [`CrowdmarkError`] if the request to Crowdmark fails.
     #[inline]
-    pub async fn start_drafting(
-        &self,
-        csrf: &str,
-        assessment_id: &str,
-    ) -> Result<(), CrowdmarkError> {
-        self.client.post(format!("https://app.crowdmark.com/api/v2/student/assignments/{assessment_id}/start-drafting"))
-            .header("X-Csrf-Token", csrf)
-            .send().await?;
+    pub async fn start_drafting(&self, assignment_id: &AssignmentId) -> Result<(), CrowdmarkError> {
+        let request = self.client.post(self.endpoint(&format!(
+            "api/v2/student/assignments/{assignment_id}/start-drafting"
+        ))?);
+        HttpError::check(self.execute(request).await?, Method::POST).await?;
         Ok(())
     }
 
@@ -158,8 +121,7 @@ impl crate::Client {
     #[inline]
     pub async fn submit_assessment(
         &self,
-        csrf: &str,
-        assessment_id: &str,
+        assessment_id: &AssessmentId,
     ) -> Result<(), CrowdmarkError> {
         #[derive(Debug, Serialize)]
         struct TargetOutput {
@@ -181,40 +143,40 @@ impl crate::Client {
             upload_signature: String,
         }
 
-        let root = self.fetch_assessment(assessment_id).await?;
+        let root = self.get_assessment(assessment_id).await?;
 
         let pages: Vec<_> = root
-            .included
-            .into_iter()
-            .filter(|i| i.type_ == "assignment-pages")
-            .map(|i| {
-                let question_id = i
-                    .relationships
-                    .as_ref()
-                    .and_then(|r| r.question.as_ref())
-                    .and_then(|q| q.data.as_ref())
-                    .map(|d| match d.id.clone() {
-                        Value::String(s) => s.clone(),
-                        Value::Number(n) => n.to_string(),
-                        _ => String::new(),
-                    })
-                    .unwrap_or_default();
-
-                TargetPage {
-                    id: i.id,
-                    question_id,
-                    filename: i.attributes.filename.unwrap_or_default(),
-                    uuid: i.attributes.uuid.unwrap_or_default(),
-                    number: i.attributes.number.unwrap_or_default(),
-                }
+            .questions
+            .iter()
+            .flat_map(|question| {
+                question
+                    .pages
+                    .iter()
+                    .map(move |page| (question.id.to_string(), page))
+            })
+            .chain(
+                root.unassigned_pages
+                    .iter()
+                    .map(|page| (String::new(), page)),
+            )
+            .map(|(question_id, page)| TargetPage {
+                id: page.id.to_string(),
+                question_id,
+                filename: page.filename.clone(),
+                uuid: page.uuid.clone(),
+                number: page.number,
             })
             .collect();
 
         let s3_policy_response = self
-            .client
-            .post("https://app.crowdmark.com/api/v1/s3_policies")
-            .form(&[("enrollment_uuid", root.data.id.clone())])
-            .send()
+            .send(|| {
+                Ok(self
+                    .client
+                    .post(self.endpoint("api/v1/s3_policies")?)
+                    .form(&[("enrollment_uuid", root.assignment_id.as_str())]))
+            })
+            .await?;
+        let s3_policy_response = HttpError::check(s3_policy_response, Method::POST)
             .await?
             .json::<S3Response>()
             .await?;
@@ -223,22 +185,22 @@ impl crate::Client {
 
         let output = TargetOutput { pages, signature };
 
-        self.client
-            .put(format!(
-                "https://app.crowdmark.com/api/v2/student/assignments/{}",
-                root.data.id
-            ))
-            .json(&output)
-            .header("X-Csrf-Token", csrf)
-            .send()
-            .await?
-            .error_for_status()
-            .map_err(|msg| CrowdmarkError::AssessmentSubmit(msg.to_string()))?;
+        let request = self
+            .client
+            .put(self.endpoint(&format!(
+                "api/v2/student/assignments/{}",
+                root.assignment_id
+            ))?)
+            .json(&output);
+        HttpError::check(self.execute(request).await?, Method::PUT)
+            .await
+            .map_err(CrowdmarkError::AssessmentSubmit)?;
 
         Ok(())
     }
 
-    /// Uploads pages for an assessment.
+    /// Uploads pages for an assessment, replacing its current draft. The
+    /// page at index `n` answers question `n + 1`.
     ///
     /// # Errors
     ///
@@ -249,139 +211,581 @@ impl crate::Client {
     #[inline]
     pub async fn upload_assessment<I>(
         &self,
-        csrf: &str,
-        assessment_id: &str,
+        assessment_id: &AssessmentId,
         pages: I,
     ) -> Result<(), CrowdmarkError>
     where
         I: IntoIterator<Item = (usize, Vec<u8>)>,
     {
-        let root = self.fetch_assessment(assessment_id).await?;
-        let assignment_id = root.data.id.clone();
-        let shared_root = Arc::new(root);
-        self.start_drafting(csrf, &assignment_id).await?;
-        self.clear_pages(csrf, Arc::<AssessResponse>::clone(&shared_root))
-            .await?;
+        let mut map = PageMap::new();
+        let mut images = Vec::new();
+        for (question, img) in pages {
+            images.push(img);
+            map.insert(question + 1, [images.len()]);
+        }
+
+        let draft = self.get_assessment(assessment_id).await?;
+        if map
+            .iter()
+            .any(|(question, _)| !draft.questions.iter().any(|q| q.sequence == question))
+        {
+            return Err(CrowdmarkError::TooManyPages());
+        }
+
+        let options = UploadOptions::new();
+        self.upload_pages(draft, &map, images.len(), channel(images), &options)
+            .await
+    }
+
+    /// Uploads pages for an assessment according to `map`. Page `n` in `map`
+    /// refers to `pages[n - 1]`; questions answered by several pages get one
+    /// anchored page each. `options` decides which existing draft pages are
+    /// kept, how many pages are uploaded at once and whether progress is
+    /// journaled.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CrowdmarkError` if:
+    /// - The assessment ID is invalid.
+    /// - `map` refers to a question or page that does not exist.
+    /// - The journal cannot be read or written.
+    /// - Requests to S3 or Crowdmark fail.
+    #[inline]
+    pub async fn upload_mapped_assessment(
+        &self,
+        assessment_id: &AssessmentId,
+        map: &PageMap,
+        pages: Vec<Vec<u8>>,
+        options: &UploadOptions,
+    ) -> Result<(), CrowdmarkError> {
+        let draft = self.get_assessment(assessment_id).await?;
+        self.upload_pages(draft, map, pages.len(), channel(pages), options)
+            .await
+    }
 
+    /// Like [`upload_mapped_assessment`](Self::upload_mapped_assessment), but
+    /// receives the `page_count` pages in order while earlier ones are
+    /// uploading, so that producing pages and uploading them overlap. Pages
+    /// are only received as fast as they are uploaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns `CrowdmarkError` if:
+    /// - The assessment ID is invalid.
+    /// - `map` refers to a question that does not exist or a page beyond
+    ///   `page_count`.
+    /// - `pages` closes before `page_count` pages were received.
+    /// - The journal cannot be read or written.
+    /// - Requests to S3 or Crowdmark fail.
+    #[inline]
+    pub async fn upload_streamed_assessment(
+        &self,
+        assessment_id: &AssessmentId,
+        map: &PageMap,
+        page_count: usize,
+        pages: mpsc::Receiver<Vec<u8>>,
+        options: &UploadOptions,
+    ) -> Result<(), CrowdmarkError> {
+        let draft = self.get_assessment(assessment_id).await?;
+        self.upload_pages(draft, map, page_count, pages, options)
+            .await
+    }
+
+    /// Uploads the pages `map` assigns and only then deletes the draft
+    /// pages the mode replaces. A failure at any point is rolled back to the
+    /// previous draft, while the journal, if any, keeps the images already
+    /// sent to S3 for the next attempt to register again.
+    async fn upload_pages(
+        &self,
+        draft: AssessmentDetail,
+        map: &PageMap,
+        page_count: usize,
+        mut pages: mpsc::Receiver<Vec<u8>>,
+        options: &UploadOptions,
+    ) -> Result<(), CrowdmarkError> {
+        // Questions each PDF page answers.
+        let mut targets: BTreeMap<usize, Vec<&Question>> = BTreeMap::new();
+        for (sequence, numbers) in map.iter() {
+            let question = draft
+                .questions
+                .iter()
+                .find(|q| q.sequence == sequence)
+                .ok_or(CrowdmarkError::UnknownQuestion(sequence))?;
+            for &number in numbers {
+                if !(1..=page_count).contains(&number) {
+                    return Err(CrowdmarkError::MissingPage(number));
+                }
+                targets.entry(number).or_default().push(question);
+            }
+        }
+
+        let journal = options.journal.as_deref().map(Journal::open).transpose()?;
+        // Pages an interrupted attempt created and never rolled back, which
+        // new pages were numbered after.
+        let journaled: Vec<_> = draft
+            .pages()
+            .filter(|page| journal.as_ref().is_some_and(|j| j.contains(&page.id)))
+            .map(|page| page.id.clone())
+            .collect();
+
+        let targeted = |question: &Question| map.get(question.sequence).is_some();
+        let replaced_questions: Vec<_> = match options.mode {
+            UploadMode::Replace => draft.questions.iter().collect(),
+            UploadMode::ReplaceQuestions => {
+                draft.questions.iter().filter(|q| targeted(q)).collect()
+            }
+            UploadMode::Append => Vec::new(),
+        };
+        let mut replaced_pages: Vec<_> = replaced_questions
+            .iter()
+            .flat_map(|question| question.pages.iter().map(|page| (Some(&question.id), page)))
+            .collect();
+        if options.mode == UploadMode::Replace {
+            replaced_pages.extend(draft.unassigned_pages.iter().map(|page| (None, page)));
+        }
+        // Number new pages after the ones that are kept so they sort last.
+        let first_number = draft
+            .pages()
+            .filter(|page| {
+                !journaled.contains(&page.id)
+                    && !replaced_pages
+                        .iter()
+                        .any(|(_, replaced)| replaced.id == page.id)
+            })
+            .map(|page| page.number)
+            .max()
+            .and_then(|number| usize::try_from(number).ok())
+            .unwrap_or_default();
+
+        self.start_drafting(&draft.assignment_id).await?;
+
+        let context = Arc::new(UploadContext {
+            assessment_id: draft.id.clone(),
+            assignment_id: draft.assignment_id.clone(),
+            client: self.clone(),
+            first_number,
+            journal,
+            progress: options.progress.clone(),
+        });
         let mut set = tokio::task::JoinSet::new();
+        let mut created = Vec::new();
+        let mut resumed = Vec::new();
+        let mut failure = None;
+        let mut received = 0;
+
+        'pages: while let Some(img) = pages.recv().await {
+            received += 1;
+            let number = received;
+            let Some(questions) = targets.get(&number) else {
+                continue;
+            };
+            let hash = context.journal.as_ref().map(|_| journal::hash(&img));
+            for question in questions {
+                let entry = context
+                    .journal
+                    .as_ref()
+                    .zip(hash.as_deref())
+                    .and_then(|(journal, hash)| journal.find(hash, &question.id, number));
+                // Pages whose assignment page survives from an interrupted
+                // attempt are kept as they are.
+                if let Some(page_id) = entry.as_ref().and_then(|entry| {
+                    question
+                        .pages
+                        .iter()
+                        .find(|page| entry.page_id.as_ref() == Some(&page.id))
+                        .map(|page| &page.id)
+                }) {
+                    context.report(UploadEvent::PageRegistered {
+                        page: number,
+                        page_id: page_id.clone(),
+                        question_id: question.id.clone(),
+                    });
+                    resumed.push(page_id.clone());
+                    continue;
+                }
 
-        for (question, img) in pages {
-            let client = self.client.clone();
-
-            let cloned_assignment_id = assignment_id.clone();
-            let cloned_root = Arc::<AssessResponse>::clone(&shared_root);
-            let cloned_csrf = csrf.to_owned();
-            set.spawn(async move {
-                upload_page(
-                    client,
-                    cloned_root,
-                    cloned_csrf,
-                    &cloned_assignment_id,
-                    question + 1,
-                    img,
-                )
-                .await
-            });
+                while set.len() >= options.jobs {
+                    if let Some(result) = set.join_next().await {
+                        collect(result, &mut created, &mut failure);
+                    }
+                }
+                if failure.is_some() {
+                    break 'pages;
+                }
+                let upload = PageUpload {
+                    hash: hash.clone(),
+                    img: img.clone(),
+                    number,
+                    question_id: question.id.clone(),
+                    resumed: entry,
+                };
+                let context = Arc::<UploadContext>::clone(&context);
+                set.spawn(async move { context.upload_page(upload).await });
+            }
+        }
+        // Stop whoever produces the pages if uploading was abandoned.
+        drop(pages);
+        if failure.is_none() && received < page_count {
+            failure = Some(CrowdmarkError::MissingPage(received + 1));
         }
 
+        // Let every task finish so that all created pages are known before
+        // rolling back.
         while let Some(result) = set.join_next().await {
-            result??;
+            collect(result, &mut created, &mut failure);
+        }
+
+        replaced_pages.retain(|(_, page)| !resumed.contains(&page.id));
+        let mut deleted = Vec::new();
+        let mut unanchored = Vec::new();
+        if failure.is_none() {
+            for &(question_id, page) in &replaced_pages {
+                if let Err(err) = self.delete_draft_page(&page.id).await {
+                    failure = Some(err);
+                    break;
+                }
+                deleted.push((question_id, page));
+            }
+        }
+        if failure.is_none() {
+            // Questions that received new pages were re-anchored to them.
+            for question in replaced_questions.iter().filter(|q| !targeted(q)) {
+                if let Err(err) = self
+                    .anchor_question(&draft.assignment_id, &question.id, None)
+                    .await
+                {
+                    failure = Some(err);
+                    break;
+                }
+                unanchored.push(*question);
+            }
+        }
+
+        let Some(error) = failure else {
+            if let Some(journal) = &context.journal {
+                journal.remove()?;
+            }
+            return Ok(());
+        };
+        // Questions whose anchor may have moved: those given new pages, those
+        // that lost pages and those that were unanchored.
+        let touched: Vec<_> = draft
+            .questions
+            .iter()
+            .filter(|question| {
+                (!created.is_empty() && targeted(question))
+                    || deleted.iter().any(|(id, _)| *id == Some(&question.id))
+                    || unanchored.iter().any(|q| q.id == question.id)
+            })
+            .collect();
+        match self
+            .restore_draft(&draft, &created, &deleted, &touched)
+            .await
+        {
+            Ok(()) => Err(error),
+            Err(rollback) => Err(CrowdmarkError::RollbackFailed {
+                error: Box::new(error),
+                rollback: Box::new(rollback),
+            }),
         }
+    }
 
+    /// Undoes a partial upload by deleting the pages it created,
+    /// re-attaching the previously uploaded images of the pages it deleted
+    /// and anchoring every question it touched back to its original page.
+    async fn restore_draft(
+        &self,
+        draft: &AssessmentDetail,
+        created: &[PageId],
+        deleted: &[(Option<&QuestionId>, &DraftPage)],
+        touched: &[&Question],
+    ) -> Result<(), CrowdmarkError> {
+        for &(question_id, page) in deleted {
+            self.create_page(
+                &draft.id,
+                &draft.assignment_id,
+                question_id,
+                false,
+                page.number,
+                &page.filename,
+                &page.uuid,
+            )
+            .await?;
+        }
+        for question in touched {
+            self.anchor_question(
+                &draft.assignment_id,
+                &question.id,
+                question.anchor.as_deref(),
+            )
+            .await?;
+        }
+        for page_id in created {
+            self.delete_draft_page(page_id).await?;
+        }
         Ok(())
     }
+
+    /// Attaches an image already uploaded to S3 as a draft page of
+    /// `assessment_id`, making it the page its question is anchored to if `anchor` is set.
+    /// Before retrying, the draft is checked for the page in case an earlier
+    /// attempt went through but its response was lost.
+    #[expect(clippy::too_many_arguments)]
+    async fn create_page(
+        &self,
+        assessment_id: &AssessmentId,
+        assignment_id: &AssignmentId,
+        question_id: Option<&QuestionId>,
+        anchor: bool,
+        number: i64,
+        filename: &str,
+        uuid: &str,
+    ) -> Result<PageId, CrowdmarkError> {
+        let question = question_id.map(|id| {
+            serde_json::json!({
+                "type": "assignment-questions",
+                "id": id
+            })
+        });
+        let mut attributes = serde_json::json!({
+            "number": number,
+            "filename": filename,
+            "uuid": uuid,
+        });
+        if anchor {
+            attributes["is-anchor"] = serde_json::Value::Bool(true);
+        }
+        let body = serde_json::json!({
+            "data": {
+                "type": "assignment-pages",
+                "attributes": attributes,
+                "relationships": {
+                    "assignment": { "data": { "id": assignment_id, "type": "assignments" } },
+                    "question": { "data": question }
+                }
+            }
+        });
+
+        let mut attempt = 0;
+        let response = loop {
+            let request = self
+                .client
+                .post(self.endpoint("api/v2/student/assignment-pages")?)
+                .header("Content-Type", "application/vnd.api+json")
+                .json(&body);
+            let result = self.execute(request).await;
+            let Some(delay) = self.retry.delay(attempt, &result) else {
+                break result?;
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+
+            let draft = self.get_assessment(assessment_id).await?;
+            if let Some(page) = draft.pages().find(|page| page.uuid == uuid) {
+                return Ok(page.id.clone());
+            }
+        };
+
+        let document = HttpError::check(response, Method::POST)
+            .await
+            .map_err(CrowdmarkError::AssessmentUpload)?
+            .json::<Document<RawResource>>()
+            .await?;
+        Ok(PageId::from_trusted(document.data::<PageAttributes>()?.id))
+    }
 }
 
-async fn upload_page(
-    client: reqwest::Client,
-    root: Arc<AssessResponse>,
-    csrf: String,
-    assignment_id: &str,
-    question: usize,
-    img: Vec<u8>,
-) -> Result<(), CrowdmarkError> {
-    #[derive(Deserialize)]
-    struct S3Response {
-        bucket: String,
-        fields: Vec<(String, String)>,
-        key: String,
+/// Feeds pages that are already in memory through the channel uploads read
+/// from.
+fn channel(pages: Vec<Vec<u8>>) -> mpsc::Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel(pages.len().max(1));
+    for page in pages {
+        // The channel has room for every page and the receiver is alive, so
+        // this cannot fail.
+        let _sent = sender.try_send(page);
     }
+    receiver
+}
 
-    let question_id = root
-        .included
-        .iter()
-        .find(|i| i.type_ == "assignment-questions" && i.attributes.sequence == Some(question))
-        .map(|i| i.id.clone())
-        .ok_or(CrowdmarkError::TooManyPages())?;
-
-    let uuid = generate_uuid_v4();
-
-    let s3_policy = client
-        .post("https://app.crowdmark.com/api/v1/s3_policies")
-        .form(&[
-            ("enrollment_uuid", assignment_id),
-            ("requested_uuid", uuid.as_str()),
-            ("original_filename", assignment_id),
-            ("content_type", "image/jpeg"),
-        ])
-        .send()
-        .await?
-        .json::<S3Response>()
-        .await?;
-
-    let mut form = multipart::Form::new();
-
-    for (name, value) in s3_policy.fields {
-        form = form.text(name, value);
+/// Records the outcome of a finished upload task, keeping the first failure.
+fn collect(
+    result: Result<Result<PageId, CrowdmarkError>, tokio::task::JoinError>,
+    created: &mut Vec<PageId>,
+    failure: &mut Option<CrowdmarkError>,
+) {
+    match result.map_err(CrowdmarkError::from).and_then(|page| page) {
+        Ok(page_id) => created.push(page_id),
+        Err(err) => {
+            failure.get_or_insert(err);
+        }
     }
+}
 
-    form = form
-        .text("key", s3_policy.key)
-        .text("Content-Type", "image/jpeg")
-        .text("x-amz-meta-original-filename", assignment_id.to_owned())
-        .part(
-            "file",
-            multipart::Part::stream(img).file_name(assignment_id.to_owned()),
-        );
-
-    client
-        .post(s3_policy.bucket)
-        .multipart(form)
-        .send()
-        .await?
-        .error_for_status()
-        .map_err(|msg| CrowdmarkError::S3Upload(msg.to_string()))?;
-
-    let body = serde_json::json!({
-        "data": {
-            "type": "assignment-pages",
-            "attributes": {
-                "number": question,
-                "filename": assignment_id,
-                "uuid": uuid,
-                "is-anchor": true,
-            },
-            "relationships": {
-                "question": {
-                    "data": {
-                        "type": "assignment-questions",
-                        "id": question_id
-                    }
-                }
+/// A page image waiting to be uploaded.
+struct PageUpload {
+    /// Set when the upload is journaled.
+    hash: Option<String>,
+    img: Vec<u8>,
+    /// Page number within the uploaded PDF.
+    number: usize,
+    question_id: QuestionId,
+    /// Journal entry left by an earlier attempt at this page.
+    resumed: Option<JournalEntry>,
+}
+
+/// State shared by the tasks uploading pages of one assignment.
+struct UploadContext {
+    assessment_id: AssessmentId,
+    assignment_id: AssignmentId,
+    client: crate::Client,
+    /// Offset added to PDF page numbers so new pages sort after kept ones.
+    first_number: usize,
+    journal: Option<Journal>,
+    progress: Option<UnboundedSender<UploadEvent>>,
+}
+
+impl UploadContext {
+    /// Uploads a page image to S3 and attaches it to its question, skipping
+    /// the S3 upload if an earlier attempt already finished it.
+    async fn upload_page(&self, upload: PageUpload) -> Result<PageId, CrowdmarkError> {
+        let (uuid, s3_key) = match &upload.resumed {
+            Some(entry) => (entry.uuid.clone(), entry.s3_key.clone()),
+            None => {
+                let uuid = generate_uuid_v4();
+                self.report(UploadEvent::UploadStarted {
+                    page: upload.number,
+                    total: upload.img.len() as u64,
+                });
+                let s3_key = self
+                    .upload_image(&uuid, upload.number, upload.img.clone())
+                    .await?;
+                self.report(UploadEvent::UploadFinished {
+                    page: upload.number,
+                });
+                self.record(&upload, &uuid, &s3_key, None)?;
+                (uuid, s3_key)
             }
+        };
+
+        let page_id = self
+            .client
+            .create_page(
+                &self.assessment_id,
+                &self.assignment_id,
+                Some(&upload.question_id),
+                true,
+                i64::try_from(self.first_number + upload.number).unwrap_or(i64::MAX),
+                self.assignment_id.as_str(),
+                &uuid,
+            )
+            .await?;
+        self.record(&upload, &uuid, &s3_key, Some(&page_id))?;
+        self.report(UploadEvent::PageRegistered {
+            page: upload.number,
+            page_id: page_id.clone(),
+            question_id: upload.question_id,
+        });
+        Ok(page_id)
+    }
+
+    /// Uploads the image of page `number` to S3 under `uuid`, returning its
+    /// S3 key.
+    async fn upload_image(
+        &self,
+        uuid: &str,
+        number: usize,
+        img: Vec<u8>,
+    ) -> Result<String, CrowdmarkError> {
+        #[derive(Deserialize)]
+        struct S3Response {
+            bucket: String,
+            fields: Vec<(String, String)>,
+            key: String,
         }
-    });
-
-    client
-        .post("https://app.crowdmark.com/api/v2/student/assignment-pages")
-        .header("Content-Type", "application/vnd.api+json")
-        .header("X-Csrf-Token", csrf)
-        .json(&body)
-        .send()
-        .await?
-        .error_for_status()
-        .map_err(|msg| CrowdmarkError::AssessmentUpload(msg.to_string()))?;
-    Ok(())
+
+        let assignment_id = &self.assignment_id;
+        let client = &self.client;
+        let s3_policy = client
+            .send(|| {
+                Ok(client
+                    .client
+                    .post(client.endpoint("api/v1/s3_policies")?)
+                    .form(&[
+                        ("enrollment_uuid", assignment_id.as_str()),
+                        ("requested_uuid", uuid),
+                        ("original_filename", assignment_id.as_str()),
+                        ("content_type", "image/jpeg"),
+                    ]))
+            })
+            .await?;
+        let s3_policy = HttpError::check(s3_policy, Method::POST)
+            .await?
+            .json::<S3Response>()
+            .await?;
+
+        // Every attempt posts the same key, so a retry overwrites rather than
+        // duplicates the object.
+        let response = client
+            .send_external(|| {
+                let mut form = multipart::Form::new();
+
+                for (name, value) in &s3_policy.fields {
+                    form = form.text(name.clone(), value.clone());
+                }
+
+                form = form
+                    .text("key", s3_policy.key.clone())
+                    .text("Content-Type", "image/jpeg")
+                    .text("x-amz-meta-original-filename", assignment_id.to_string())
+                    .part(
+                        "file",
+                        multipart::Part::stream_with_length(
+                            Body::wrap_stream(ProgressBody::new(
+                                img.clone(),
+                                number,
+                                self.progress.clone(),
+                            )),
+                            img.len() as u64,
+                        )
+                        .file_name(assignment_id.to_string()),
+                    );
+
+                Ok(client.client.post(&s3_policy.bucket).multipart(form))
+            })
+            .await?;
+        HttpError::check(response, Method::POST)
+            .await
+            .map_err(CrowdmarkError::S3Upload)?;
+
+        Ok(s3_policy.key)
+    }
+
+    /// Sends `event` to the progress channel, if there is one.
+    fn report(&self, event: UploadEvent) {
+        progress::report(self.progress.as_ref(), event);
+    }
+
+    /// Saves a page's S3 upload and, once created, its assignment page to
+    /// the journal, if there is one.
+    fn record(
+        &self,
+        upload: &PageUpload,
+        uuid: &str,
+        s3_key: &str,
+        page_id: Option<&PageId>,
+    ) -> Result<(), CrowdmarkError> {
+        let (Some(journal), Some(hash)) = (&self.journal, &upload.hash) else {
+            return Ok(());
+        };
+        journal.record(JournalEntry {
+            hash: hash.clone(),
+            number: upload.number,
+            page_id: page_id.cloned(),
+            question_id: upload.question_id.clone(),
+            s3_key: s3_key.to_owned(),
+            uuid: uuid.to_owned(),
+        })
+    }
 }
 
 fn generate_uuid_v4() -> String {
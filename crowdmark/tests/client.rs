@@ -0,0 +1,1302 @@
+use crowdmark::error::{CrowdmarkError, ErrorKind};
+use crowdmark::{
+    AssessmentId, AssessmentKind, Client, ClientBuilder, PageMap, PasswordSession, RetryPolicy,
+    SessionFuture, SessionProvider, StaticSession, SubmissionState, UploadEvent, UploadMode,
+    UploadOptions,
+};
+use crowdmark_mock::MockServer;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+async fn authenticated(server: &MockServer) -> Client {
+    let token = server.state().add_session();
+    ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_token(&token)
+        .retry_policy(RetryPolicy::new().initial_backoff(Duration::from_millis(1)))
+        .build()
+        .expect("Failed to build client")
+}
+
+/// Adds a course with an assignment of `questions` questions and returns the
+/// assignment's assessment ID.
+fn draft_assignment(server: &MockServer, questions: usize) -> AssessmentId {
+    let mut state = server.state();
+    let course = state.add_course("MATH 101", false);
+    state
+        .add_assignment(&course, "Assignment 1", questions)
+        .exam_master_id
+        .parse()
+        .expect("Invalid assessment ID")
+}
+
+#[tokio::test]
+async fn login_returns_session_token() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+
+    let token = ClientBuilder::new()
+        .base_url(server.base_url())
+        .login("student@example.com".to_owned(), "hunter2".to_owned())
+        .await
+        .expect("Login failed");
+
+    assert!(server.state().sessions.contains(&token));
+}
+
+#[tokio::test]
+async fn login_rejects_wrong_password() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+
+    let result = ClientBuilder::new()
+        .base_url(server.base_url())
+        .login("student@example.com".to_owned(), "wrong".to_owned())
+        .await;
+
+    assert!(matches!(result, Err(CrowdmarkError::Login())));
+}
+
+#[tokio::test]
+async fn list_courses_reports_archived_courses() {
+    let server = MockServer::start().await;
+    let current = server.state().add_course("MATH 101", false);
+    let archived = server.state().add_course("MATH 100", true);
+    server.state().add_assignment(&current, "Assignment 1", 3);
+    let client = authenticated(&server).await;
+
+    let courses = client.list_courses().await.expect("Failed to list courses");
+
+    assert_eq!(courses.len(), 2);
+    assert_eq!(courses[0].id.as_str(), current);
+    assert_eq!(courses[0].name, "MATH 101");
+    assert_eq!(courses[0].assessment_count, 1);
+    assert!(!courses[0].archived);
+    assert_eq!(courses[1].id.as_str(), archived);
+    assert!(courses[1].archived);
+}
+
+#[tokio::test]
+async fn list_assessments_filters_by_course() {
+    let server = MockServer::start().await;
+    let course = server.state().add_course("MATH 101", false);
+    let other = server.state().add_course("PHYS 101", false);
+    let exam_master_id = {
+        let mut state = server.state();
+        let assignment = state.add_assignment(&course, "Assignment 1", 2);
+        assignment.normalized_points = Some(0.75);
+        assignment.exam_master_id.clone()
+    };
+    server.state().add_assignment(&other, "Lab 1", 1);
+    let client = authenticated(&server).await;
+
+    let assessments = client
+        .list_assessments(&course.parse().expect("Invalid course ID"))
+        .await
+        .expect("Failed to list assessments");
+
+    assert_eq!(assessments.len(), 1);
+    assert_eq!(assessments[0].id.as_str(), exam_master_id);
+    assert_eq!(assessments[0].title, "Assignment 1");
+    assert_eq!(assessments[0].score, Some(0.75));
+    assert!(matches!(assessments[0].kind, AssessmentKind::TakeHome));
+}
+
+#[tokio::test]
+async fn upload_then_submit_round_trips_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    client
+        .submit_assessment(&assessment_id)
+        .await
+        .expect("Submit failed");
+
+    let state = server.state();
+    let assignment = state
+        .assignment(assessment_id.as_str())
+        .expect("Missing assignment");
+    assert!(assignment.submitted_at.is_some());
+    assert_eq!(assignment.submitted_pages.len(), 2);
+    for (question, page) in assignment.questions.iter().zip([&b"first"[..], b"second"]) {
+        let uploaded = assignment
+            .pages
+            .iter()
+            .find(|p| p.question_id.as_ref() == Some(&question.id))
+            .expect("Question has no page");
+        let object = &state.s3_objects[&format!("uploads/{}", uploaded.uuid)];
+        assert_eq!(object.data, page);
+        assert_eq!(object.content_type, "image/jpeg");
+    }
+}
+
+#[tokio::test]
+async fn upload_replaces_existing_draft() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+
+    for _ in 0..2 {
+        let pages = [b"first".to_vec(), b"second".to_vec()]
+            .into_iter()
+            .enumerate();
+        client
+            .upload_assessment(&assessment_id, pages)
+            .await
+            .expect("Upload failed");
+    }
+
+    let state = server.state();
+    let assignment = state
+        .assignment(assessment_id.as_str())
+        .expect("Missing assignment");
+    assert_eq!(assignment.pages.len(), 2);
+    assert!(assignment.drafting);
+}
+
+#[tokio::test]
+async fn upload_rejects_too_many_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+
+    assert!(matches!(result, Err(CrowdmarkError::TooManyPages())));
+}
+
+#[tokio::test]
+async fn get_assessment_groups_draft_pages_by_question() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 3);
+    let due = chrono::Utc::now();
+    {
+        let mut state = server.state();
+        let assignment = state
+            .assignment_mut(assessment_id.as_str())
+            .expect("Missing assignment");
+        assignment.due = Some(due);
+        assignment.late_due = Some(due + chrono::Duration::days(1));
+        assignment.questions[1].points = 4.0;
+    }
+    let client = authenticated(&server).await;
+
+    let detail = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(detail.id, assessment_id);
+    assert_eq!(detail.title, "Assignment 1");
+    assert_eq!(detail.state, SubmissionState::NotStarted);
+    assert_eq!(detail.due, Some(due));
+    assert_eq!(detail.late_due, Some(due + chrono::Duration::days(1)));
+    assert_eq!(detail.questions.len(), 3);
+    assert_eq!(detail.questions[1].label, "Q2");
+    assert_eq!(detail.questions[1].max_points, Some(4.0));
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+
+    let detail = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(detail.state, SubmissionState::Drafting);
+    assert_eq!(detail.questions[0].pages.len(), 1);
+    assert_eq!(detail.questions[1].pages.len(), 1);
+    assert!(detail.questions[2].pages.is_empty());
+    assert!(detail.unassigned_pages.is_empty());
+
+    client
+        .submit_assessment(&assessment_id)
+        .await
+        .expect("Submit failed");
+    let detail = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(detail.state, SubmissionState::Submitted);
+}
+
+#[tokio::test]
+async fn get_feedback_requires_marks_to_be_sent() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+
+    let result = client.get_feedback(&assessment_id).await;
+
+    assert!(matches!(result, Err(CrowdmarkError::NotGraded())));
+}
+
+#[tokio::test]
+async fn get_feedback_returns_scores_comments_and_annotated_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    client
+        .submit_assessment(&assessment_id)
+        .await
+        .expect("Submit failed");
+
+    {
+        let mut state = server.state();
+        let assignment = state
+            .assignment_mut(assessment_id.as_str())
+            .expect("Missing assignment");
+        assignment.marks_sent_at = Some(chrono::Utc::now());
+        assignment.normalized_points = Some(0.5);
+        assignment.questions[0].score = Some(1.0);
+        assignment.questions[0].comments = vec!["Nice work".to_owned()];
+        assignment.questions[1].score = Some(0.0);
+        let page_id = assignment
+            .pages
+            .iter()
+            .find(|p| p.question_id.as_ref() == Some(&assignment.questions[0].id))
+            .expect("Question has no page")
+            .id
+            .clone();
+        state.annotate_page(&page_id, b"marked".to_vec());
+    }
+
+    let feedback = client
+        .get_feedback(&assessment_id)
+        .await
+        .expect("Failed to get feedback");
+
+    assert_eq!(feedback.score, Some(0.5));
+    assert_eq!(feedback.questions.len(), 2);
+    assert_eq!(feedback.questions[0].points, Some(1.0));
+    assert_eq!(feedback.questions[0].max_points, Some(1.0));
+    assert_eq!(feedback.questions[0].comments, ["Nice work"]);
+    assert_eq!(feedback.questions[0].pages.len(), 1);
+    assert_eq!(feedback.questions[1].points, Some(0.0));
+    assert!(feedback.questions[1].comments.is_empty());
+    assert!(feedback.questions[1].pages.is_empty());
+
+    let image = client
+        .download_annotated_page(&feedback.questions[0].pages[0])
+        .await
+        .expect("Failed to download annotated page");
+    assert_eq!(image, b"marked");
+}
+
+#[tokio::test]
+async fn download_submission_returns_uploaded_images_by_question() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 3);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+
+    let submission = client
+        .download_submission(&assessment_id)
+        .await
+        .expect("Failed to download submission");
+
+    assert_eq!(submission.assessment_id, assessment_id);
+    assert_eq!(submission.questions.len(), 3);
+    assert_eq!(submission.questions[0].pages.len(), 1);
+    assert_eq!(submission.questions[0].pages[0].image, b"first");
+    assert_eq!(submission.questions[1].pages[0].image, b"second");
+    assert!(submission.questions[2].pages.is_empty());
+    assert!(submission.unassigned_pages.is_empty());
+}
+
+#[tokio::test]
+async fn download_submission_returns_unassigned_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    server
+        .state()
+        .assignment_mut(assessment_id.as_str())
+        .expect("Missing assignment")
+        .pages[1]
+        .question_id = None;
+
+    let submission = client
+        .download_submission(&assessment_id)
+        .await
+        .expect("Failed to download submission");
+
+    assert_eq!(submission.questions[1].pages.len(), 0);
+    assert_eq!(submission.unassigned_pages.len(), 1);
+    assert_eq!(submission.unassigned_pages[0].image, b"second");
+}
+
+#[tokio::test]
+async fn upload_mapped_assessment_anchors_several_pages_per_question() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let map: PageMap = "1=1-2,2=3".parse().expect("Invalid page map");
+    let pages = vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+
+    client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            pages.clone(),
+            &UploadOptions::new().mode(UploadMode::Replace),
+        )
+        .await
+        .expect("Upload failed");
+
+    let submission = client
+        .download_submission(&assessment_id)
+        .await
+        .expect("Failed to download submission");
+    let images: Vec<Vec<_>> = submission
+        .questions
+        .iter()
+        .map(|q| q.pages.iter().map(|p| p.image.as_slice()).collect())
+        .collect();
+    assert_eq!(images, [vec![&b"first"[..], b"second"], vec![b"third"]]);
+
+    for (map, error) in [("3=1", "Question 3"), ("1=4", "Page 4")] {
+        let map: PageMap = map.parse().expect("Invalid page map");
+        let result = client
+            .upload_mapped_assessment(
+                &assessment_id,
+                &map,
+                pages.clone(),
+                &UploadOptions::new().mode(UploadMode::Replace),
+            )
+            .await;
+        let message = result.expect_err("Upload should fail").to_string();
+        assert!(message.starts_with(error), "{message}");
+    }
+}
+
+#[tokio::test]
+async fn upload_modes_keep_untargeted_draft_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+
+    let map: PageMap = "2=1".parse().expect("Invalid page map");
+    client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            vec![b"revised".to_vec()],
+            &UploadOptions::new().mode(UploadMode::ReplaceQuestions),
+        )
+        .await
+        .expect("Upload failed");
+    client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            vec![b"appended".to_vec()],
+            &UploadOptions::new().mode(UploadMode::Append),
+        )
+        .await
+        .expect("Upload failed");
+
+    let submission = client
+        .download_submission(&assessment_id)
+        .await
+        .expect("Failed to download submission");
+    let images: Vec<Vec<_>> = submission
+        .questions
+        .iter()
+        .map(|q| q.pages.iter().map(|p| p.image.as_slice()).collect())
+        .collect();
+    assert_eq!(
+        images,
+        [vec![&b"first"[..]], vec![&b"revised"[..], b"appended"]]
+    );
+}
+
+#[tokio::test]
+async fn draft_pages_can_be_moved_reordered_and_deleted() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let map: PageMap = "1=1-3".parse().expect("Invalid page map");
+    let pages = vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+    client
+        .upload_mapped_assessment(&assessment_id, &map, pages, &UploadOptions::new())
+        .await
+        .expect("Upload failed");
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    let ids: Vec<_> = draft.questions[0]
+        .pages
+        .iter()
+        .map(|page| page.id.clone())
+        .collect();
+
+    client
+        .move_draft_page(&ids[2], &draft.questions[1].id)
+        .await
+        .expect("Move failed");
+    client
+        .reorder_draft_pages(&assessment_id, &[ids[1].clone()])
+        .await
+        .expect("Reorder failed");
+    let numbers: Vec<_> = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment")
+        .pages()
+        .map(|page| (page.id.clone(), page.number))
+        .collect();
+    assert_eq!(
+        numbers,
+        [
+            (ids[1].clone(), 1),
+            (ids[0].clone(), 2),
+            (ids[2].clone(), 3)
+        ]
+    );
+    assert!(matches!(
+        client
+            .reorder_draft_pages(
+                &assessment_id,
+                &["999999".parse().expect("Invalid page ID")]
+            )
+            .await,
+        Err(CrowdmarkError::UnknownPage(_))
+    ));
+    client
+        .delete_draft_page(&ids[1])
+        .await
+        .expect("Delete failed");
+
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    let pages: Vec<Vec<_>> = draft
+        .questions
+        .iter()
+        .map(|q| q.pages.iter().map(|p| p.id.clone()).collect())
+        .collect();
+    assert_eq!(pages, [vec![ids[0].clone()], vec![ids[2].clone()]]);
+}
+
+#[tokio::test]
+async fn failed_upload_restores_previous_draft() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    let before = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+
+    server.state().page_limit = Some(3);
+    let pages = [b"new first".to_vec(), b"new second".to_vec()]
+        .into_iter()
+        .enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+    assert!(matches!(result, Err(CrowdmarkError::AssessmentUpload(_))));
+
+    let after = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    let ids = |detail: &crowdmark::AssessmentDetail| -> Vec<Vec<_>> {
+        detail
+            .questions
+            .iter()
+            .map(|q| q.pages.iter().map(|p| p.id.clone()).collect())
+            .collect()
+    };
+    assert_eq!(ids(&after), ids(&before));
+}
+
+#[tokio::test]
+async fn failed_unanchoring_reanchors_questions() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 3);
+    let questions: Vec<_> = {
+        let mut state = server.state();
+        let assignment = state
+            .assignment_mut(assessment_id.as_str())
+            .expect("Missing assignment");
+        for question in &mut assignment.questions {
+            question.anchored = true;
+        }
+        assignment.questions.iter().map(|q| q.id.clone()).collect()
+    };
+    let client = authenticated(&server).await;
+    server.state().add_fault(
+        "PATCH",
+        &format!("/api/v2/student/assignment-questions/{}", questions[2]),
+        400,
+    );
+
+    let map: PageMap = "1=1".parse().expect("Invalid page map");
+    let result = client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            vec![b"first".to_vec()],
+            &UploadOptions::new(),
+        )
+        .await;
+    assert!(matches!(result, Err(CrowdmarkError::Http(_))));
+
+    let state = server.state();
+    let assignment = state
+        .assignment(assessment_id.as_str())
+        .expect("Missing assignment");
+    assert!(assignment.questions.iter().all(|q| q.anchored));
+    assert!(assignment.pages.is_empty());
+}
+
+#[tokio::test]
+async fn failed_upload_restores_unassigned_pages_and_anchors() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 3);
+    let client = authenticated(&server).await;
+    let map: PageMap = "1=1-2,2=3".parse().expect("Invalid page map");
+    let pages = vec![b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+    client
+        .upload_mapped_assessment(&assessment_id, &map, pages, &UploadOptions::new())
+        .await
+        .expect("Upload failed");
+    let snapshot = |server: &MockServer| {
+        let state = server.state();
+        let assignment = state
+            .assignment(assessment_id.as_str())
+            .expect("Missing assignment");
+        let mut pages: Vec<_> = assignment
+            .pages
+            .iter()
+            .map(|p| (p.question_id.clone(), p.number, p.uuid.clone()))
+            .collect();
+        pages.sort();
+        let anchored: Vec<_> = assignment.questions.iter().map(|q| q.anchored).collect();
+        (pages, anchored)
+    };
+    // Leave question 2's page unassigned and question 2 unanchored.
+    let third = {
+        let mut state = server.state();
+        let assignment = state
+            .assignment_mut(assessment_id.as_str())
+            .expect("Missing assignment");
+        assignment.pages[2].question_id = None;
+        assignment.questions[1].anchored = false;
+        assignment.questions[2].anchored = true;
+        assignment.questions[2].id.clone()
+    };
+    let before = snapshot(&server);
+
+    server.state().add_fault(
+        "PATCH",
+        &format!("/api/v2/student/assignment-questions/{third}"),
+        400,
+    );
+    let map: PageMap = "2=1".parse().expect("Invalid page map");
+    let result = client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            vec![b"new".to_vec()],
+            &UploadOptions::new(),
+        )
+        .await;
+    assert!(matches!(result, Err(CrowdmarkError::Http(_))));
+
+    assert_eq!(snapshot(&server), before);
+}
+
+#[tokio::test]
+async fn journaled_upload_rolls_back_then_resumes() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let journal = std::env::temp_dir().join(format!(
+        "crowdmark-journal-{}-{assessment_id}.json",
+        std::process::id()
+    ));
+    let options = UploadOptions::new().journal(&journal);
+    let map: PageMap = "1=1,2=2".parse().expect("Invalid page map");
+    let pages = vec![b"first".to_vec(), b"second".to_vec()];
+    client
+        .upload_mapped_assessment(&assessment_id, &map, pages.clone(), &UploadOptions::new())
+        .await
+        .expect("Upload failed");
+    let page_ids = |detail: &crowdmark::AssessmentDetail| -> Vec<_> {
+        detail.pages().map(|page| page.id.clone()).collect()
+    };
+    let before = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+
+    server.state().page_limit = Some(3);
+    let pages = vec![b"new first".to_vec(), b"new second".to_vec()];
+    let result = client
+        .upload_mapped_assessment(&assessment_id, &map, pages.clone(), &options)
+        .await;
+    assert!(result.is_err());
+    assert!(journal.exists());
+    let after = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(page_ids(&after), page_ids(&before));
+    assert_eq!(server.state().s3_objects.len(), 4);
+
+    server.state().page_limit = None;
+    client
+        .upload_mapped_assessment(&assessment_id, &map, pages, &options)
+        .await
+        .expect("Resumed upload failed");
+    assert!(!journal.exists());
+    assert_eq!(server.state().s3_objects.len(), 4);
+
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(draft.pages().count(), 2);
+    assert!(
+        !draft
+            .pages()
+            .any(|page| before.pages().any(|p| p.uuid == page.uuid))
+    );
+}
+
+#[tokio::test]
+async fn resumed_upload_keeps_journaled_pages() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let journal = std::env::temp_dir().join(format!(
+        "crowdmark-journal-{}-{assessment_id}-kept.json",
+        std::process::id()
+    ));
+    let options = UploadOptions::new().jobs(1).journal(&journal);
+    let map: PageMap = "1=1,2=2".parse().expect("Invalid page map");
+    let pages = vec![b"first".to_vec(), b"second".to_vec()];
+
+    // The first page is created but cannot be deleted again when the second
+    // fails.
+    server.state().page_limit = Some(1);
+    server
+        .state()
+        .add_fault("PATCH", "/api/v2/student/assignment-pages/", 500);
+    let result = client
+        .upload_mapped_assessment(&assessment_id, &map, pages.clone(), &options)
+        .await;
+    assert!(matches!(result, Err(CrowdmarkError::RollbackFailed { .. })));
+    let kept = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment")
+        .questions[0]
+        .pages[0]
+        .id
+        .clone();
+
+    server.state().page_limit = None;
+    client
+        .upload_mapped_assessment(&assessment_id, &map, pages, &options)
+        .await
+        .expect("Resumed upload failed");
+    assert!(!journal.exists());
+    assert_eq!(server.state().s3_objects.len(), 2);
+
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(draft.pages().count(), 2);
+    assert_eq!(draft.questions[0].pages[0].id, kept);
+}
+
+#[tokio::test]
+async fn streamed_upload_receives_pages_while_uploading() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let map: PageMap = "1=1-2,2=3".parse().expect("Invalid page map");
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    let producer = tokio::spawn(async move {
+        for page in [&b"first"[..], b"second", b"third"] {
+            sender
+                .send(page.to_vec())
+                .await
+                .expect("Upload stopped early");
+        }
+    });
+    client
+        .upload_streamed_assessment(
+            &assessment_id,
+            &map,
+            3,
+            receiver,
+            &UploadOptions::new().jobs(1),
+        )
+        .await
+        .expect("Upload failed");
+    producer.await.expect("Producer panicked");
+
+    let submission = client
+        .download_submission(&assessment_id)
+        .await
+        .expect("Failed to download submission");
+    let images: Vec<Vec<_>> = submission
+        .questions
+        .iter()
+        .map(|q| q.pages.iter().map(|p| p.image.as_slice()).collect())
+        .collect();
+    assert_eq!(images, [vec![&b"first"[..], b"second"], vec![b"third"]]);
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    sender
+        .send(b"only".to_vec())
+        .await
+        .expect("Failed to send page");
+    drop(sender);
+    let result = client
+        .upload_streamed_assessment(&assessment_id, &map, 3, receiver, &UploadOptions::new())
+        .await;
+    assert!(matches!(result, Err(CrowdmarkError::MissingPage(2))));
+}
+
+#[tokio::test]
+async fn upload_reports_progress_events() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+    let map: PageMap = "1=1".parse().expect("Invalid page map");
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let image = vec![0; 100_000];
+
+    client
+        .upload_mapped_assessment(
+            &assessment_id,
+            &map,
+            vec![image],
+            &UploadOptions::new().progress(sender),
+        )
+        .await
+        .expect("Upload failed");
+
+    let mut events = Vec::new();
+    while let Some(event) = receiver.recv().await {
+        events.push(event);
+    }
+    assert!(matches!(
+        events.first(),
+        Some(UploadEvent::UploadStarted {
+            page: 1,
+            total: 100_000
+        })
+    ));
+    assert!(events.iter().any(|event| matches!(
+        event,
+        UploadEvent::UploadProgress {
+            page: 1,
+            sent: 100_000,
+            total: 100_000
+        }
+    )));
+    assert!(matches!(
+        &events[events.len() - 2..],
+        [
+            UploadEvent::UploadFinished { page: 1 },
+            UploadEvent::PageRegistered { page: 1, .. }
+        ]
+    ));
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+    {
+        let mut state = server.state();
+        state
+            .add_fault("GET", "/api/v2/student/assignments", 503)
+            .retry_after = Some(0);
+        state.add_fault("POST", "/api/v1/s3_policies", 429);
+        state.add_fault("POST", "/s3", 502);
+    }
+
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+
+    let state = server.state();
+    assert!(state.faults.is_empty());
+    let s3_uploads = state
+        .requests
+        .iter()
+        .filter(|r| r.method == "POST" && r.path == "/s3")
+        .count();
+    assert_eq!(s3_uploads, 2);
+    assert_eq!(state.assignments[0].pages.len(), 1);
+}
+
+#[tokio::test]
+async fn lost_page_registration_is_not_duplicated() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+    server
+        .state()
+        .add_fault("POST", "/api/v2/student/assignment-pages", 502)
+        .after_handling = true;
+
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+
+    let state = server.state();
+    let registrations = state
+        .requests
+        .iter()
+        .filter(|r| r.method == "POST" && r.path == "/api/v2/student/assignment-pages")
+        .count();
+    assert_eq!(registrations, 1);
+    assert_eq!(state.assignments[0].pages.len(), 1);
+}
+
+#[tokio::test]
+async fn retries_can_be_disabled() {
+    let server = MockServer::start().await;
+    let token = server.state().add_session();
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_token(&token)
+        .retry_policy(RetryPolicy::none())
+        .build()
+        .expect("Failed to build client");
+    server
+        .state()
+        .add_fault("GET", "/api/v2/student/courses", 503);
+
+    assert!(client.list_courses().await.is_err());
+    assert_eq!(server.state().requests.len(), 1);
+}
+
+#[tokio::test]
+async fn rate_limit_spaces_out_requests() {
+    let server = MockServer::start().await;
+    let token = server.state().add_session();
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_token(&token)
+        .rate_limit(20.0, 2)
+        .build()
+        .expect("Failed to build client");
+
+    let start = std::time::Instant::now();
+    for _ in 0..6 {
+        client.list_courses().await.expect("Failed to list courses");
+    }
+
+    // Two requests fit in the burst; the other four wait 50 ms each.
+    assert!(start.elapsed() >= Duration::from_millis(190));
+    assert_eq!(server.state().requests.len(), 6);
+}
+
+#[tokio::test]
+async fn failed_responses_are_not_decoded() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+
+    server
+        .state()
+        .add_fault("GET", "/api/v2/student/assignments", 404);
+    let result = client.get_assessment(&assessment_id).await;
+    assert!(matches!(result, Err(CrowdmarkError::Http(err)) if err.status == 404));
+
+    server.state().add_fault("POST", "/api/v1/s3_policies", 400);
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+    assert!(matches!(
+        result,
+        Err(CrowdmarkError::Http(err)) if err.path == "/api/v1/s3_policies"
+    ));
+}
+
+#[tokio::test]
+async fn rate_limit_applies_to_logging_in() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_provider(PasswordSession::new(
+            "student@example.com".to_owned(),
+            "hunter2".to_owned(),
+        ))
+        .rate_limit(20.0, 1)
+        .build()
+        .expect("Failed to build client");
+
+    let start = std::time::Instant::now();
+    client.list_courses().await.expect("Failed to list courses");
+
+    // Fetching the sign-in form fits in the burst; signing in and listing
+    // courses wait 50 ms each.
+    assert!(start.elapsed() >= Duration::from_millis(90));
+    assert_eq!(server.state().requests.len(), 3);
+}
+
+#[tokio::test]
+async fn failed_requests_report_status_and_body() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+
+    let page_id = "page-404".parse().expect("Invalid page ID");
+    let Err(CrowdmarkError::Http(err)) = client.delete_draft_page(&page_id).await else {
+        panic!("Deleting a missing page succeeded");
+    };
+    assert_eq!(err.method, reqwest::Method::PATCH);
+    assert_eq!(err.path, "/api/v2/student/assignment-pages/page-404");
+    assert_eq!(err.status, reqwest::StatusCode::NOT_FOUND);
+    assert!(err.body.contains("No assignment-page with id page-404"));
+    assert_eq!(err.kind(), ErrorKind::Client);
+
+    server.state().add_fault("POST", "/s3", 403);
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+    let Err(err) = result else {
+        panic!("Upload succeeded despite S3 refusing it");
+    };
+    assert_eq!(err.kind(), Some(ErrorKind::Client));
+    assert!(matches!(err, CrowdmarkError::S3Upload(ref source) if source.path == "/s3"));
+}
+
+#[tokio::test]
+async fn forbidden_requests_do_not_expire_the_session() {
+    let server = MockServer::start().await;
+    let client = authenticated(&server).await;
+    server
+        .state()
+        .add_fault("GET", "/api/v2/student/courses", 403);
+
+    let Err(CrowdmarkError::Http(err)) = client.list_courses().await else {
+        panic!("Forbidden request succeeded");
+    };
+    assert_eq!(err.status, reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(err.kind(), ErrorKind::Client);
+    assert_eq!(server.state().requests.len(), 1);
+}
+
+#[tokio::test]
+async fn validation_errors_are_not_resent() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+    server
+        .state()
+        .add_fault("POST", "/api/v2/student/assignment-pages", 422);
+
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+    assert!(matches!(
+        result,
+        Err(CrowdmarkError::AssessmentUpload(err))
+            if err.status == 422 && err.path == "/api/v2/student/assignment-pages"
+    ));
+    let registrations = server
+        .state()
+        .requests
+        .iter()
+        .filter(|r| r.method == "POST" && r.path == "/api/v2/student/assignment-pages")
+        .count();
+    assert_eq!(registrations, 1);
+}
+
+#[tokio::test]
+async fn csrf_token_is_cached_and_refreshed_when_rejected() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 2);
+    let client = authenticated(&server).await;
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    let token_fetches = |server: &MockServer| {
+        server
+            .state()
+            .requests
+            .iter()
+            .filter(|r| r.method == "GET" && r.path == "/student")
+            .count()
+    };
+    assert_eq!(token_fetches(&server), 1);
+
+    "rotated-csrf-token".clone_into(&mut server.state().csrf_token);
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    client
+        .delete_draft_page(&draft.questions[0].pages[0].id)
+        .await
+        .expect("Delete failed");
+    client
+        .delete_draft_page(&draft.questions[1].pages[0].id)
+        .await
+        .expect("Delete failed");
+
+    assert_eq!(token_fetches(&server), 2);
+    let draft = client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+    assert_eq!(draft.pages().count(), 0);
+}
+
+#[tokio::test]
+async fn expired_session_is_reported_as_not_authenticated() {
+    let server = MockServer::start().await;
+    let assessment_id = draft_assignment(&server, 1);
+    let client = authenticated(&server).await;
+    client
+        .get_assessment(&assessment_id)
+        .await
+        .expect("Failed to get assessment");
+
+    server.state().sessions.clear();
+    let result = client.get_assessment(&assessment_id).await;
+    assert!(matches!(result, Err(CrowdmarkError::NotAuthenticated(_))));
+    let pages = [b"first".to_vec()].into_iter().enumerate();
+    let result = client.upload_assessment(&assessment_id, pages).await;
+    assert!(matches!(result, Err(CrowdmarkError::NotAuthenticated(_))));
+
+    let client = authenticated(&server).await;
+    server
+        .state()
+        .add_fault("GET", "/api/v2/student/courses", 401);
+    let result = client.list_courses().await;
+    assert!(matches!(result, Err(CrowdmarkError::NotAuthenticated(_))));
+}
+
+#[tokio::test]
+async fn session_provider_logs_in_again_when_session_expires() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+    let assessment_id = draft_assignment(&server, 2);
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_provider(PasswordSession::new(
+            "student@example.com".to_owned(),
+            "hunter2".to_owned(),
+        ))
+        .build()
+        .expect("Failed to build client");
+
+    client.list_courses().await.expect("Failed to list courses");
+    assert_eq!(server.state().sessions.len(), 1);
+
+    server.state().sessions.clear();
+    let pages = [b"first".to_vec(), b"second".to_vec()]
+        .into_iter()
+        .enumerate();
+    client
+        .upload_assessment(&assessment_id, pages)
+        .await
+        .expect("Upload failed");
+    server.state().sessions.clear();
+    client
+        .submit_assessment(&assessment_id)
+        .await
+        .expect("Submit failed");
+
+    let sign_ins = server
+        .state()
+        .requests
+        .iter()
+        .filter(|r| r.method == "POST" && r.path == "/sign-in")
+        .count();
+    assert_eq!(sign_ins, 3);
+}
+
+/// Logs in with fixed credentials and records which tokens were validated.
+#[derive(Debug, Default)]
+struct RecordingSession {
+    validated: Arc<Mutex<Vec<String>>>,
+}
+
+impl SessionProvider for RecordingSession {
+    fn session_token<'a>(
+        &'a self,
+        builder: &'a ClientBuilder,
+        _expired: Option<&'a str>,
+    ) -> SessionFuture<'a> {
+        Box::pin(builder.login("student@example.com".to_owned(), "hunter2".to_owned()))
+    }
+
+    fn session_validated(&self, token: &str, _expires: Option<chrono::DateTime<chrono::Utc>>) {
+        self.validated
+            .lock()
+            .expect("Poisoned lock")
+            .push(token.to_owned());
+    }
+}
+
+#[tokio::test]
+async fn session_provider_is_told_once_each_token_is_accepted() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+    let provider = RecordingSession::default();
+    let validated = Arc::clone(&provider.validated);
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_provider(provider)
+        .build()
+        .expect("Failed to build client");
+
+    client.list_courses().await.expect("Failed to list courses");
+    client.list_courses().await.expect("Failed to list courses");
+    let first = server.state().sessions.iter().next().cloned();
+    assert_eq!(
+        validated.lock().expect("Poisoned lock").first(),
+        first.as_ref()
+    );
+
+    server.state().sessions.clear();
+    client.list_courses().await.expect("Failed to list courses");
+    let second = server.state().sessions.iter().next().cloned();
+    let validated = validated.lock().expect("Poisoned lock");
+    assert_eq!(validated.len(), 2);
+    assert_eq!(validated.last(), second.as_ref());
+}
+
+#[tokio::test]
+async fn session_provider_is_told_about_rotated_tokens() {
+    let server = MockServer::start().await;
+    server.state().add_user("student@example.com", "hunter2");
+    server.state().rotate_sessions = true;
+    let provider = RecordingSession::default();
+    let validated = Arc::clone(&provider.validated);
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_provider(provider)
+        .build()
+        .expect("Failed to build client");
+
+    client.list_courses().await.expect("Failed to list courses");
+    client.list_courses().await.expect("Failed to list courses");
+
+    let current = server.state().sessions.iter().next().cloned();
+    let validated = validated.lock().expect("Poisoned lock");
+    assert_eq!(validated.len(), 2);
+    assert_eq!(validated.last(), current.as_ref());
+}
+
+#[tokio::test]
+async fn debug_output_hides_session_tokens() {
+    let server = MockServer::start().await;
+    let token = server.state().add_session();
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_token(&token)
+        .build()
+        .expect("Failed to build client");
+    client.list_courses().await.expect("Failed to list courses");
+    let session = client.export_session().expect("Failed to export session");
+
+    let builder = ClientBuilder::new()
+        .base_url(server.base_url())
+        .session_token(&token)
+        .exported_session(&session);
+    let restored = builder.clone().build().expect("Failed to restore session");
+    for debug in [
+        format!("{client:?}"),
+        format!("{builder:?}"),
+        format!("{restored:?}"),
+        format!("{:?}", StaticSession::new(&token)),
+    ] {
+        assert!(!debug.contains(&token), "{debug} leaks the session token");
+    }
+}
+
+#[tokio::test]
+async fn exported_session_keeps_refreshed_cookies() {
+    let server = MockServer::start().await;
+    server.state().add_course("MATH 101", false);
+    let client = authenticated(&server).await;
+    server.state().rotate_sessions = true;
+
+    client.list_courses().await.expect("Failed to list courses");
+    client.list_courses().await.expect("Failed to list courses");
+    let session = client.export_session().expect("Failed to export session");
+    drop(client);
+
+    let client = ClientBuilder::new()
+        .base_url(server.base_url())
+        .exported_session(&session)
+        .build()
+        .expect("Failed to restore session");
+    let courses = client.list_courses().await.expect("Failed to list courses");
+    assert_eq!(courses.len(), 1);
+
+    server.state().sessions.clear();
+    let result = client.list_courses().await;
+    assert!(matches!(result, Err(CrowdmarkError::NotAuthenticated(_))));
+}
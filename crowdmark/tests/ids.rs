@@ -0,0 +1,31 @@
+use crowdmark::error::CrowdmarkError;
+use crowdmark::{AssessmentId, CourseId};
+
+#[test]
+fn ids_round_trip_through_strings_and_json() {
+    let id: CourseId = "math135-f25".parse().expect("Invalid course ID");
+
+    assert_eq!(id.to_string(), "math135-f25");
+    assert_eq!(
+        serde_json::to_string(&id).expect("Failed to serialize"),
+        r#""math135-f25""#
+    );
+    assert_eq!(
+        serde_json::from_str::<CourseId>(r#""math135-f25""#).expect("Failed to deserialize"),
+        id
+    );
+}
+
+#[test]
+fn ids_reject_characters_that_would_change_the_url() {
+    for invalid in ["", "..", "a/b", "a?b", "a b", "a#b", "%2e%2e"] {
+        assert!(
+            matches!(
+                invalid.parse::<AssessmentId>(),
+                Err(CrowdmarkError::InvalidAssessmentID())
+            ),
+            "{invalid:?} should be rejected"
+        );
+    }
+    assert!(serde_json::from_str::<CourseId>(r#""../admin""#).is_err());
+}
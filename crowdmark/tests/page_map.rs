@@ -0,0 +1,27 @@
+use crowdmark::PageMap;
+use crowdmark::error::CrowdmarkError;
+
+#[test]
+fn page_map_parses_ranges_and_round_trips() {
+    let map: PageMap = "1=1-2, 2=3,3=4-6,1=8".parse().expect("Invalid page map");
+
+    assert_eq!(map.get(1), Some(&[1, 2, 8][..]));
+    assert_eq!(map.get(2), Some(&[3][..]));
+    assert_eq!(map.get(3), Some(&[4, 5, 6][..]));
+    assert_eq!(map.get(4), None);
+    assert_eq!(map.to_string(), "1=1-2,1=8,2=3,3=4-6");
+    assert_eq!(map.to_string().parse::<PageMap>().ok(), Some(map));
+}
+
+#[test]
+fn page_map_rejects_malformed_entries() {
+    for invalid in ["", "1", "1=", "=1", "0=1", "1=0", "1=3-2", "1=a", "1=1-2-3"] {
+        assert!(
+            matches!(
+                invalid.parse::<PageMap>(),
+                Err(CrowdmarkError::InvalidPageMap(_))
+            ),
+            "{invalid:?} should be rejected"
+        );
+    }
+}
@@ -1,13 +1,15 @@
 use crate::OutputFormat;
 use crate::error::ClimarkError;
 use comfy_table::{Attribute::Bold, Cell, Color, Table};
+use crowdmark::CourseId;
 
 pub async fn list_assessments(
     client: crowdmark::Client,
     course_id: &str,
     format: &OutputFormat,
 ) -> Result<(), ClimarkError> {
-    let assessments = client.list_assessments(course_id).await?;
+    let course_id: CourseId = course_id.parse()?;
+    let assessments = client.list_assessments(&course_id).await?;
 
     match *format {
         OutputFormat::Json => println!("{}", serde_json::to_string(&assessments)?),
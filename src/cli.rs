@@ -19,6 +19,42 @@ pub enum OutputFormat {
 #[derive(clap::Subcommand)]
 #[non_exhaustive]
 pub enum Commands {
+    #[command(about = "Inspect and edit an assessment's draft", subcommand)]
+    Draft(DraftCommands),
+    #[command(about = "Download submitted pages")]
+    DownloadSubmission {
+        #[arg(num_args = 1..=2)]
+        ids: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+        #[arg(
+            help = "Save pages to this directory [default: current directory]",
+            short,
+            long
+        )]
+        output_dir: Option<std::path::PathBuf>,
+        #[arg(
+            help = "Write pages to a PDF instead",
+            long,
+            conflicts_with = "output_dir"
+        )]
+        pdf: Option<std::path::PathBuf>,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
+    #[command(about = "Show graded feedback")]
+    Feedback {
+        #[arg(num_args = 1..=2)]
+        ids: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+        #[arg(help = "Save annotated pages to this directory", short, long)]
+        output_dir: Option<std::path::PathBuf>,
+        #[arg(help = "Write annotated pages and comments to a PDF", long)]
+        pdf: Option<std::path::PathBuf>,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
     #[command(about = "List assessments")]
     ListAssessments {
         #[arg(env = "CLIMARK_DEFAULT_COURSE")]
@@ -43,9 +79,116 @@ pub enum Commands {
         ids: Vec<String>,
         #[arg(help = "Output scale of PDF", long, default_value_t = 3.0)]
         scale: f32,
+        #[arg(
+            help = "Pages answering each question, e.g. 1=1-2,2=3 [default: one page per question]",
+            short,
+            long
+        )]
+        map: Option<String>,
+        #[arg(
+            help = "Map pages to questions using PDF bookmarks",
+            long_help = "Map pages to questions using PDF bookmarks titled like \"Question 3\" \
+                         or \"Q3\"",
+            long,
+            conflicts_with = "map"
+        )]
+        map_from_outline: bool,
+        #[arg(
+            help = "Map pages to questions using headings like \"Question 4\" in the PDF text",
+            long,
+            conflicts_with_all = ["map", "map_from_outline"]
+        )]
+        map_from_text: bool,
+        #[arg(help = "Upload an inferred page map without asking", short, long)]
+        yes: bool,
+        #[arg(
+            help = "Replace only these questions' pages, keeping the rest of the draft",
+            long_help = "Replace only these questions' pages, keeping the rest of the draft, \
+                         e.g. --only-question 3 or --only-question 2,4",
+            long,
+            value_name = "QUESTION",
+            value_delimiter = ',',
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        only_question: Vec<usize>,
+        #[arg(
+            help = "Add pages to the draft without removing any",
+            long,
+            conflicts_with = "only_question"
+        )]
+        append: bool,
+        #[arg(
+            help = "Number of pages to upload at once",
+            short,
+            long,
+            default_value_t = 4,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        jobs: usize,
+        #[arg(
+            help = "Progress output; json prints one event per line",
+            short,
+            long,
+            value_enum,
+            default_value_t
+        )]
+        format: OutputFormat,
         #[arg(help = "Don't print error messages", long)]
         silent: bool,
         #[arg(help = "Don't submit assessment after upload", short, long)]
         nosubmit: bool,
     },
 }
+
+#[derive(clap::Subcommand)]
+#[non_exhaustive]
+pub enum DraftCommands {
+    #[command(about = "Delete draft pages")]
+    Delete {
+        #[arg(num_args = 1..=2)]
+        ids: Vec<String>,
+        #[arg(
+            help = "Pages to delete, e.g. --pages 12,10",
+            long,
+            value_delimiter = ',',
+            required = true
+        )]
+        pages: Vec<String>,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
+    #[command(about = "List draft pages")]
+    List {
+        #[arg(num_args = 1..=2)]
+        ids: Vec<String>,
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
+    #[command(about = "Move a draft page to another question")]
+    Move {
+        #[arg(num_args = 1..=2, required = true)]
+        ids: Vec<String>,
+        #[arg(help = "Page to move", required = true)]
+        page: String,
+        #[arg(help = "Sequence number of the target question", long)]
+        to_question: usize,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
+    #[command(about = "Renumber draft pages, putting the pages given first")]
+    Reorder {
+        #[arg(num_args = 1..=2)]
+        ids: Vec<String>,
+        #[arg(
+            help = "Pages to put first, in order, e.g. --pages 12,10",
+            long,
+            value_delimiter = ',',
+            required = true
+        )]
+        pages: Vec<String>,
+        #[arg(help = "Don't print error messages", short, long)]
+        silent: bool,
+    },
+}
@@ -0,0 +1,95 @@
+use crate::OutputFormat;
+use crate::error::ClimarkError;
+use crate::pdf;
+use comfy_table::{Attribute::Bold, Cell, Color, Table};
+use crowdmark::{AssessmentId, Client, Submission};
+use std::fs;
+use std::path::Path;
+
+pub async fn download_submission(
+    client: Client,
+    assessment_id: &str,
+    format: &OutputFormat,
+    output_dir: Option<&Path>,
+    pdf_path: Option<&Path>,
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let submission = client.download_submission(&assessment_id).await?;
+
+    if let Some(path) = pdf_path {
+        fs::write(path, submission_pdf(&submission)?)?;
+    } else {
+        let dir = output_dir.unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)?;
+        for question in &submission.questions {
+            for (index, page) in question.pages.iter().enumerate() {
+                let name = format!("q{}-p{}.jpg", question.sequence, index + 1);
+                fs::write(dir.join(name), &page.image)?;
+            }
+        }
+        for (index, page) in submission.unassigned_pages.iter().enumerate() {
+            let name = format!("unassigned-p{}.jpg", index + 1);
+            fs::write(dir.join(name), &page.image)?;
+        }
+    }
+
+    match *format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&submission)?),
+        OutputFormat::Plain => {
+            use std::io::{self, Write as _};
+            let stdout = io::stdout();
+            let mut handle = io::BufWriter::new(stdout.lock());
+            for question in &submission.questions {
+                writeln!(handle, "{}\t{}", question.label, question.pages.len())?;
+            }
+            if !submission.unassigned_pages.is_empty() {
+                writeln!(handle, "Unassigned\t{}", submission.unassigned_pages.len())?;
+            }
+        }
+        OutputFormat::Pretty => {
+            let mut table = Table::new();
+            table.load_preset(crate::TABLE_PRESET).set_header(vec![
+                Cell::new("Question").add_attribute(Bold),
+                Cell::new("Pages").add_attribute(Bold),
+            ]);
+            for question in &submission.questions {
+                table.add_row([
+                    Cell::new(&question.label).fg(Color::Green),
+                    Cell::new(question.pages.len()).fg(Color::Magenta),
+                ]);
+            }
+            if !submission.unassigned_pages.is_empty() {
+                table.add_row([
+                    Cell::new("Unassigned").fg(Color::Yellow),
+                    Cell::new(submission.unassigned_pages.len()).fg(Color::Magenta),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lays out every submitted page in question order, followed by unassigned
+/// pages, labelling the first page of each group.
+fn submission_pdf(submission: &Submission) -> Result<Vec<u8>, ClimarkError> {
+    let pages: Vec<_> = submission
+        .questions
+        .iter()
+        .map(|question| (question.label.as_str(), &question.pages))
+        .chain([("Unassigned", &submission.unassigned_pages)])
+        .flat_map(|(label, pages)| {
+            pages
+                .iter()
+                .enumerate()
+                .map(move |(index, page)| pdf::Page {
+                    heading: (index == 0).then(|| label.to_owned()),
+                    image: Some(page.image.clone()),
+                    ..Default::default()
+                })
+        })
+        .collect();
+
+    pdf::render(&pages)
+}
@@ -0,0 +1,119 @@
+use crate::OutputFormat;
+use crate::error::ClimarkError;
+use comfy_table::{Attribute::Bold, Cell, Color, Table};
+use crowdmark::error::CrowdmarkError;
+use crowdmark::{AssessmentId, Client, PageId};
+
+pub async fn delete(
+    client: Client,
+    assessment_id: &str,
+    pages: &[String],
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let pages = parse_pages(pages)?;
+    let draft = client.get_assessment(&assessment_id).await?;
+    if let Some(page) = pages
+        .iter()
+        .find(|page| !draft.pages().any(|draft_page| draft_page.id == **page))
+    {
+        return Err(CrowdmarkError::UnknownPage(page.to_string()).into());
+    }
+    for page in &pages {
+        client.delete_draft_page(page).await?;
+    }
+    Ok(())
+}
+
+pub async fn list(
+    client: Client,
+    assessment_id: &str,
+    format: &OutputFormat,
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let draft = client.get_assessment(&assessment_id).await?;
+    let pages: Vec<_> = draft
+        .questions
+        .iter()
+        .flat_map(|question| {
+            question
+                .pages
+                .iter()
+                .map(move |page| (Some(question), page))
+        })
+        .chain(draft.unassigned_pages.iter().map(|page| (None, page)))
+        .collect();
+
+    match *format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&draft)?),
+        OutputFormat::Plain => {
+            use std::io::{self, Write as _};
+            let stdout = io::stdout();
+            let mut handle = io::BufWriter::new(stdout.lock());
+            for (question, page) in pages {
+                writeln!(
+                    handle,
+                    "{}\t{}\t{}",
+                    page.id,
+                    question.map_or("", |q| q.label.as_str()),
+                    page.number
+                )?;
+            }
+        }
+        OutputFormat::Pretty => {
+            let mut table = Table::new();
+            table.load_preset(crate::TABLE_PRESET).set_header(vec![
+                Cell::new("ID").add_attribute(Bold),
+                Cell::new("Question").add_attribute(Bold),
+                Cell::new("Number").add_attribute(Bold),
+                Cell::new("Filename").add_attribute(Bold),
+            ]);
+            for (question, page) in pages {
+                table.add_row([
+                    Cell::new(&page.id).fg(Color::Blue),
+                    Cell::new(question.map_or("-", |q| q.label.as_str())).fg(Color::Green),
+                    Cell::new(page.number).fg(Color::Magenta),
+                    Cell::new(&page.filename),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn move_page(
+    client: Client,
+    assessment_id: &str,
+    page: &str,
+    to_question: usize,
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let page: PageId = page.parse()?;
+    let draft = client.get_assessment(&assessment_id).await?;
+    let question = draft
+        .questions
+        .iter()
+        .find(|question| question.sequence == to_question)
+        .ok_or(CrowdmarkError::UnknownQuestion(to_question))?;
+    client.move_draft_page(&page, &question.id).await?;
+    Ok(())
+}
+
+pub async fn reorder(
+    client: Client,
+    assessment_id: &str,
+    pages: &[String],
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let pages = parse_pages(pages)?;
+    client.reorder_draft_pages(&assessment_id, &pages).await?;
+    Ok(())
+}
+
+fn parse_pages(pages: &[String]) -> Result<Vec<PageId>, ClimarkError> {
+    Ok(pages
+        .iter()
+        .map(|page| page.parse())
+        .collect::<Result<_, _>>()?)
+}
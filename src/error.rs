@@ -3,14 +3,28 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum ClimarkError {
+    #[error("Pass --map to say which pages answer each question given to --only-question")]
+    AmbiguousQuestions,
+    #[error("Upload cancelled")]
+    Cancelled,
     #[error(transparent)]
     Crowdmark(#[from] CrowdmarkError),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     JsonError(#[from] serde_json::Error),
+    #[error("Could not read JPEG image")]
+    JpegParse,
+    #[error("PDF text has no headings like \"Question 1\" or \"Problem 1\"")]
+    NoHeadingQuestions,
+    #[error("PDF outline has no bookmarks titled like \"Question 1\" or \"Q1\"")]
+    NoOutlineQuestions,
+    #[error("Cannot confirm the page map without a terminal; pass --yes to skip confirmation")]
+    NoTerminal,
     #[error("Could not parse PDF from stdin")]
     PdfParse,
     #[error("Failed to read stdin")]
     StdinRead,
+    #[error("Page map includes question(s) {0}, which --only-question does not select")]
+    UnselectedQuestions(String),
 }
@@ -0,0 +1,117 @@
+use crate::OutputFormat;
+use crate::error::ClimarkError;
+use crate::pdf;
+use comfy_table::{Attribute::Bold, Cell, Color, Table};
+use crowdmark::{AssessmentId, Client, Feedback, QuestionFeedback};
+use std::fs;
+use std::path::Path;
+
+pub async fn feedback(
+    client: Client,
+    assessment_id: &str,
+    format: &OutputFormat,
+    output_dir: Option<&Path>,
+    pdf_path: Option<&Path>,
+) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let feedback = client.get_feedback(&assessment_id).await?;
+
+    if output_dir.is_some() || pdf_path.is_some() {
+        let mut images = Vec::new();
+        for question in &feedback.questions {
+            let mut question_images = Vec::new();
+            for page in &question.pages {
+                question_images.push(client.download_annotated_page(page).await?);
+            }
+            images.push(question_images);
+        }
+
+        if let Some(dir) = output_dir {
+            fs::create_dir_all(dir)?;
+            for (question, question_images) in feedback.questions.iter().zip(&images) {
+                for (index, image) in question_images.iter().enumerate() {
+                    let name = format!("q{}-p{}.jpg", question.sequence, index + 1);
+                    fs::write(dir.join(name), image)?;
+                }
+            }
+        }
+
+        if let Some(path) = pdf_path {
+            fs::write(path, feedback_pdf(&feedback, images)?)?;
+        }
+    }
+
+    match *format {
+        OutputFormat::Json => println!("{}", serde_json::to_string(&feedback)?),
+        OutputFormat::Plain => {
+            use std::io::{self, Write as _};
+            let stdout = io::stdout();
+            let mut handle = io::BufWriter::new(stdout.lock());
+            for question in &feedback.questions {
+                writeln!(
+                    handle,
+                    "{}\t{}\t{}",
+                    question.label,
+                    score(question),
+                    question.comments.join(" ")
+                )?;
+            }
+        }
+        OutputFormat::Pretty => {
+            let mut table = Table::new();
+            table.load_preset(crate::TABLE_PRESET).set_header(vec![
+                Cell::new("Question").add_attribute(Bold),
+                Cell::new("Score").add_attribute(Bold),
+                Cell::new("Comments").add_attribute(Bold),
+            ]);
+            for question in &feedback.questions {
+                table.add_row([
+                    Cell::new(&question.label).fg(Color::Green),
+                    Cell::new(score(question)).fg(Color::Magenta),
+                    Cell::new(question.comments.join("\n")).fg(Color::Blue),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Lays out each question's score and comments above its first annotated
+/// page, followed by the rest of its pages, in question order.
+fn feedback_pdf(feedback: &Feedback, images: Vec<Vec<Vec<u8>>>) -> Result<Vec<u8>, ClimarkError> {
+    let mut pages = vec![pdf::Page {
+        heading: Some(feedback.title.clone()),
+        lines: feedback
+            .score
+            .map(|s| vec![format!("Score: {:.0}%", s * 100.0)])
+            .unwrap_or_default(),
+        ..Default::default()
+    }];
+
+    for (question, question_images) in feedback.questions.iter().zip(images) {
+        let mut question_images = question_images.into_iter();
+        pages.push(pdf::Page {
+            heading: Some(format!("{} ({})", question.label, score(question))),
+            image: question_images.next(),
+            lines: question.comments.clone(),
+        });
+        pages.extend(question_images.map(|image| pdf::Page {
+            image: Some(image),
+            ..Default::default()
+        }));
+    }
+
+    pdf::render(&pages)
+}
+
+fn score(question: &QuestionFeedback) -> String {
+    let points = question
+        .points
+        .map_or_else(|| "-".to_owned(), |p| p.to_string());
+    match question.max_points {
+        Some(max) => format!("{points}/{max}"),
+        None => points,
+    }
+}
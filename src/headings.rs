@@ -0,0 +1,209 @@
+use crate::error::ClimarkError;
+use crate::mapping::{self, Start};
+use crowdmark::PageMap;
+use hayro::hayro_syntax::Pdf;
+use hayro::hayro_syntax::content::ops::TypedInstruction;
+use hayro::hayro_syntax::object::Object;
+use hayro::hayro_syntax::page::Page;
+
+/// `TJ` adjustments, in thousandths of an em, wide enough to be a space.
+const SPACE_ADJUSTMENT: f32 = -250.0;
+/// Fraction of the page height treated as a running header, whose text does
+/// not count as an answer above a heading.
+const HEADER_FRACTION: f32 = 0.1;
+
+/// A run of text shown at one vertical position.
+struct Line {
+    text: String,
+    y: f32,
+}
+
+/// Builds a page map from headings such as "Question 4" or "Problem 2(b)"
+/// at the start of a line. A question starts on the page its heading
+/// appears on; if answer text precedes the heading, the previous question
+/// keeps that page too.
+///
+/// Text is decoded as Latin-1, which works for simple fonts such as those
+/// pdfLaTeX embeds but not for composite (CID) fonts.
+pub fn page_map(pdf: &Pdf) -> Result<PageMap, ClimarkError> {
+    let pages: Vec<_> = pdf
+        .pages()
+        .iter()
+        .map(|page| {
+            let media_box = page.media_box();
+            #[expect(clippy::cast_possible_truncation)]
+            let header =
+                (media_box.y1 - f64::from(HEADER_FRACTION) * (media_box.y1 - media_box.y0)) as f32;
+            (lines(page), header)
+        })
+        .collect();
+
+    mapping::assemble(&starts(&pages), pages.len()).ok_or(ClimarkError::NoHeadingQuestions)
+}
+
+/// Finds where questions start, given each page's lines and the height above
+/// which text is a running header. Only headings numbered past the current
+/// question count, so that a sentence such as "Question 2 asks..." within a
+/// later answer does not send pages back to question 2.
+fn starts(pages: &[(Vec<Line>, f32)]) -> Vec<Start> {
+    let mut starts = Vec::new();
+    let mut current = None;
+    for (index, (lines, header)) in pages.iter().enumerate() {
+        for line in lines {
+            let Some(question) = mapping::question_number(&line.text) else {
+                continue;
+            };
+            if current.is_some_and(|current| question <= current) {
+                continue;
+            }
+            starts.push(Start {
+                page: index,
+                question: Some(question),
+                shares_page: lines
+                    .iter()
+                    .any(|other| other.y > line.y + 1.0 && other.y < *header),
+            });
+            current = Some(question);
+        }
+    }
+    starts
+}
+
+/// Extracts the page's text, split into lines wherever the text position
+/// moves vertically.
+fn lines(page: &Page<'_>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut line = Line {
+        text: String::new(),
+        y: 0.0,
+    };
+    let mut y = 0.0;
+    let mut leading = 0.0;
+
+    let flush = |line: &mut Line, lines: &mut Vec<Line>| {
+        if !line.text.trim().is_empty() {
+            lines.push(Line {
+                text: std::mem::take(&mut line.text),
+                y: line.y,
+            });
+        }
+        line.text.clear();
+    };
+    let push = |line: &mut Line, y: f32, text: &[u8]| {
+        if line.text.is_empty() {
+            line.y = y;
+        }
+        line.text.extend(text.iter().map(|&b| char::from(b)));
+    };
+
+    let mut ops = page.typed_operations();
+    while let Some(op) = ops.next() {
+        match op {
+            TypedInstruction::BeginText(_) => {
+                flush(&mut line, &mut lines);
+                y = 0.0;
+            }
+            TypedInstruction::SetTextMatrix(matrix) => {
+                flush(&mut line, &mut lines);
+                y = matrix.5.as_f32();
+            }
+            TypedInstruction::NextLine(offset) => {
+                let ty = offset.1.as_f32();
+                if ty == 0.0 {
+                    line.text.push(' ');
+                } else {
+                    flush(&mut line, &mut lines);
+                    y += ty;
+                }
+            }
+            TypedInstruction::NextLineAndSetLeading(offset) => {
+                flush(&mut line, &mut lines);
+                leading = -offset.1.as_f32();
+                y += offset.1.as_f32();
+            }
+            TypedInstruction::TextLeading(value) => leading = value.0.as_f32(),
+            TypedInstruction::NextLineUsingLeading(_) => {
+                flush(&mut line, &mut lines);
+                y -= leading;
+            }
+            TypedInstruction::ShowText(text) => push(&mut line, y, text.0),
+            TypedInstruction::NextLineAndShowText(text) => {
+                flush(&mut line, &mut lines);
+                y -= leading;
+                push(&mut line, y, text.0);
+            }
+            TypedInstruction::ShowTextWithParameters(text) => {
+                flush(&mut line, &mut lines);
+                y -= leading;
+                push(&mut line, y, text.2);
+            }
+            TypedInstruction::ShowTexts(texts) => {
+                for item in texts.0.iter::<Object<'_>>() {
+                    match item {
+                        Object::String(text) => push(&mut line, y, &text),
+                        Object::Number(adjustment) if adjustment.as_f32() < SPACE_ADJUSTMENT => {
+                            line.text.push(' ');
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    flush(&mut line, &mut lines);
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Line, starts};
+
+    fn page(lines: &[(&str, f32)]) -> (Vec<Line>, f32) {
+        let lines = lines
+            .iter()
+            .map(|&(text, y)| Line {
+                text: text.to_owned(),
+                y,
+            })
+            .collect();
+        (lines, 700.0)
+    }
+
+    fn summary(pages: &[(Vec<Line>, f32)]) -> Vec<(usize, Option<usize>, bool)> {
+        starts(pages)
+            .iter()
+            .map(|start| (start.page, start.question, start.shares_page))
+            .collect()
+    }
+
+    #[test]
+    fn headings_start_questions_and_share_pages_after_answer_text() {
+        let pages = [
+            page(&[("Question 1", 650.0), ("Some answer", 600.0)]),
+            page(&[("More of the answer", 650.0), ("Question 2", 400.0)]),
+            page(&[("Header", 750.0), ("Problem 3(a)", 650.0)]),
+        ];
+
+        assert_eq!(
+            summary(&pages),
+            [(0, Some(1), false), (1, Some(2), true), (2, Some(3), false)]
+        );
+    }
+
+    #[test]
+    fn references_to_earlier_questions_are_not_headings() {
+        let pages = [
+            page(&[("Question 1", 650.0)]),
+            page(&[("Question 2", 650.0)]),
+            page(&[("Question 1 asks for a limit, so", 650.0)]),
+            page(&[("Q2 showed that", 650.0), ("Question 3", 400.0)]),
+        ];
+
+        assert_eq!(
+            summary(&pages),
+            [(0, Some(1), false), (1, Some(2), false), (3, Some(3), true)]
+        );
+    }
+}
@@ -1,40 +1,110 @@
 use crate::error::ClimarkError;
-use keyring::Entry;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use crowdmark::error::CrowdmarkError;
+use crowdmark::{Client, ClientBuilder, KeyringSession, SessionFuture, SessionProvider};
+use std::fmt;
 use std::io;
 use std::io::Write as _;
+use std::sync::OnceLock;
 
-#[derive(Deserialize, Serialize)]
-pub struct LoginDetails {
-    email: String,
-    password: String,
+/// Builds a client that starts from `token` if one was given, and otherwise
+/// logs in with the credentials in the keyring. With a token, the keyring
+/// is only opened once Crowdmark rejects it, so that nothing touches it in
+/// CI or other headless runs while the token is still good.
+pub fn client(token: Option<&str>) -> Result<Client, ClimarkError> {
+    let builder = ClientBuilder::new();
+    let builder = match token {
+        Some(token) => with_token(builder, token, stored_session),
+        None => builder.session_provider(session()?),
+    };
+    Ok(builder.build()?)
+}
+
+/// Starts `builder` from the exported `token`, falling back to the provider
+/// `open` returns once Crowdmark rejects it.
+fn with_token<P, F>(builder: ClientBuilder, token: &str, open: F) -> ClientBuilder
+where
+    P: SessionProvider + 'static,
+    F: Fn() -> Result<P, CrowdmarkError> + Send + Sync + 'static,
+{
+    builder.session_token(token).session_provider(LazySession {
+        open,
+        provider: OnceLock::new(),
+    })
+}
+
+/// A session provider that is only opened the first time a token is asked
+/// for.
+struct LazySession<P, F> {
+    open: F,
+    provider: OnceLock<P>,
+}
+
+impl<P: fmt::Debug, F> fmt::Debug for LazySession<P, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazySession")
+            .field("provider", &self.provider)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P, F> SessionProvider for LazySession<P, F>
+where
+    P: SessionProvider,
+    F: Fn() -> Result<P, CrowdmarkError> + Send + Sync,
+{
+    fn session_token<'a>(
+        &'a self,
+        builder: &'a ClientBuilder,
+        expired: Option<&'a str>,
+    ) -> SessionFuture<'a> {
+        let provider = match self.provider.get() {
+            Some(provider) => provider,
+            None => match (self.open)() {
+                Ok(provider) => self.provider.get_or_init(|| provider),
+                Err(err) => return Box::pin(async move { Err(err) }),
+            },
+        };
+        provider.session_token(builder, expired)
+    }
+
+    fn session_validated(&self, token: &str, expires: Option<DateTime<Utc>>) {
+        // Only a provider that was needed hears about tokens.
+        if let Some(provider) = self.provider.get() {
+            provider.session_validated(token, expires);
+        }
+    }
 }
 
-pub async fn get_token() -> String {
-    let entry = Entry::new(
+/// The keyring entry holding the user's credentials.
+fn keyring() -> Result<KeyringSession, CrowdmarkError> {
+    KeyringSession::new(
         "climark",
         &std::env::var("USER").expect("No user environment variable"),
     )
-    .expect("Couldn't create keyring entry: {err}");
-    let details = if let Ok(password) = entry.get_password() {
-        let details: LoginDetails =
-            serde_json::from_str(&password).expect("Failed to decode keyring JSON");
-        details
-    } else {
-        let details = LoginDetails {
-            email: get_email().expect("Failed getting email from stdin"),
-            password: get_password().expect("Failed getting password from stdin"),
-        };
+}
 
-        entry
-            .set_password(&serde_json::to_string(&details).expect("Failed to encode keyring JSON"))
-            .expect("Failed to set keyring password");
-        details
-    };
+/// The keyring entry holding the user's credentials, which are asked for
+/// the first time.
+fn session() -> Result<KeyringSession, ClimarkError> {
+    let session = keyring()?;
+    if !session.has_credentials()? {
+        session.set_credentials(&get_email()?, &get_password()?)?;
+    }
+    Ok(session)
+}
 
-    crowdmark::login::get_token(details.email, details.password)
-        .await
-        .expect("Failed to get token from Crowdmark")
+/// The keyring entry to log in with once an exported token is rejected.
+/// Nobody may be around to answer a prompt then, so the credentials must
+/// already be stored.
+fn stored_session() -> Result<KeyringSession, CrowdmarkError> {
+    let session = keyring()?;
+    if !session.has_credentials()? {
+        return Err(CrowdmarkError::NotAuthenticated(
+            "Session token was rejected and no credentials are stored".to_owned(),
+        ));
+    }
+    Ok(session)
 }
 
 fn get_email() -> Result<String, io::Error> {
@@ -52,7 +122,34 @@ fn get_password() -> Result<String, ClimarkError> {
     Ok(rpassword::read_password()?)
 }
 
-pub async fn login() {
-    let token = get_token().await;
+pub async fn login() -> Result<(), ClimarkError> {
+    let token = session()?
+        .session_token(&ClientBuilder::new(), None)
+        .await?;
     println!("export CROWDMARK_SESSION_TOKEN={token}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crowdmark_mock::MockServer;
+
+    #[tokio::test]
+    async fn expired_token_logs_in_with_stored_credentials() {
+        keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+        let server = MockServer::start().await;
+        server.state().add_user("student@example.com", "hunter2");
+
+        let builder = ClientBuilder::new().base_url(server.base_url());
+        let client = with_token(builder, "expired-token", || {
+            let session = KeyringSession::new("climark-test", "student")?;
+            session.set_credentials("student@example.com", "hunter2")?;
+            Ok(session)
+        })
+        .build()
+        .expect("Failed to build client");
+
+        client.list_courses().await.expect("Failed to list courses");
+    }
 }
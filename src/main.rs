@@ -1,12 +1,20 @@
 mod assessments;
 mod cli;
 mod courses;
+mod download;
+mod draft;
 mod error;
+mod feedback;
+mod headings;
 mod login;
+mod mapping;
+mod outline;
+mod pdf;
+mod progress;
 mod upload;
 
 use clap::Parser as _;
-use cli::{Cli, Commands, OutputFormat};
+use cli::{Cli, Commands, DraftCommands, OutputFormat};
 use error::ClimarkError;
 
 pub const TABLE_PRESET: &str = "    \u{2500}\u{2500}\u{2500}\u{2500}           ";
@@ -15,41 +23,151 @@ pub const TABLE_PRESET: &str = "    \u{2500}\u{2500}\u{2500}\u{2500}           "
 async fn main() {
     let cli = Cli::parse();
 
-    let token = match cli.crowdmark_session_token {
-        Some(t) => t,
-        None => login::get_token().await,
+    // Logging in needs no client, and building one would open the keyring
+    // a second time.
+    let result = match &cli.command {
+        Commands::Login => login::login().await,
+        command => match login::client(cli.crowdmark_session_token.as_deref()) {
+            Ok(client) => run(command, client).await,
+            Err(e) => Err(e),
+        },
     };
+    handle_error(result, is_silent(&cli.command));
+}
 
-    let client = crowdmark::Client::new(&token).expect("Failed to initialize Crowdmark client");
-
-    match cli.command {
-        Commands::ListCourses { format, silent } => {
-            handle_error(courses::list_courses(client, &format).await, silent);
+async fn run(command: &Commands, client: crowdmark::Client) -> Result<(), ClimarkError> {
+    match command {
+        Commands::Draft(DraftCommands::Delete { ids, pages, .. }) => {
+            draft::delete(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                pages,
+            )
+            .await
         }
-        Commands::ListAssessments {
-            course_id,
+        Commands::Draft(DraftCommands::List { ids, format, .. }) => {
+            draft::list(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                format,
+            )
+            .await
+        }
+        Commands::Draft(DraftCommands::Move {
+            ids,
+            page,
+            to_question,
+            ..
+        }) => {
+            draft::move_page(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                page,
+                *to_question,
+            )
+            .await
+        }
+        Commands::Draft(DraftCommands::Reorder { ids, pages, .. }) => {
+            draft::reorder(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                pages,
+            )
+            .await
+        }
+        Commands::DownloadSubmission {
+            ids,
             format,
-            silent,
-        } => handle_error(
-            assessments::list_assessments(client, &course_id, &format).await,
-            silent,
-        ),
-        Commands::Login => login::login().await,
+            output_dir,
+            pdf,
+            ..
+        } => {
+            download::download_submission(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                format,
+                output_dir.as_deref(),
+                pdf.as_deref(),
+            )
+            .await
+        }
+        Commands::Feedback {
+            ids,
+            format,
+            output_dir,
+            pdf,
+            ..
+        } => {
+            feedback::feedback(
+                client,
+                ids.last().expect("No assessment/course ID provided!"),
+                format,
+                output_dir.as_deref(),
+                pdf.as_deref(),
+            )
+            .await
+        }
+        Commands::ListCourses { format, .. } => courses::list_courses(client, format).await,
+        Commands::ListAssessments {
+            course_id, format, ..
+        } => assessments::list_assessments(client, course_id, format).await,
+        Commands::Login => unreachable!("Logging in is handled before building a client"),
         Commands::UploadAssessment {
             ids,
+            map,
+            map_from_outline,
+            map_from_text,
             scale,
-            silent,
             nosubmit,
-        } => handle_error(
+            yes,
+            only_question,
+            append,
+            jobs,
+            format,
+            ..
+        } => {
             upload::upload_assessment(
                 client,
                 ids.last().expect("No assignment/course ID provided!"),
-                scale,
-                nosubmit,
+                format,
+                match (map.as_deref(), *map_from_outline, *map_from_text) {
+                    (Some(map), _, _) => upload::MapSource::Explicit(map),
+                    (None, true, _) => upload::MapSource::Outline,
+                    (None, false, true) => upload::MapSource::Text,
+                    (None, false, false) => upload::MapSource::OnePerQuestion,
+                },
+                *scale,
+                only_question,
+                crowdmark::UploadOptions::new()
+                    .mode(match (!only_question.is_empty(), *append) {
+                        (true, _) => crowdmark::UploadMode::ReplaceQuestions,
+                        (false, true) => crowdmark::UploadMode::Append,
+                        (false, false) => crowdmark::UploadMode::Replace,
+                    })
+                    .jobs(*jobs),
+                *nosubmit,
+                *yes,
             )
-            .await,
-            silent,
-        ),
+            .await
+        }
+    }
+}
+
+/// Whether errors from `command` should be left unprinted.
+const fn is_silent(command: &Commands) -> bool {
+    match *command {
+        Commands::Draft(
+            DraftCommands::Delete { silent, .. }
+            | DraftCommands::List { silent, .. }
+            | DraftCommands::Move { silent, .. }
+            | DraftCommands::Reorder { silent, .. },
+        )
+        | Commands::DownloadSubmission { silent, .. }
+        | Commands::Feedback { silent, .. }
+        | Commands::ListCourses { silent, .. }
+        | Commands::ListAssessments { silent, .. }
+        | Commands::UploadAssessment { silent, .. } => silent,
+        Commands::Login => false,
     }
 }
 
@@ -58,5 +176,10 @@ fn handle_error(result: Result<(), ClimarkError>, silent: bool) {
         && !silent
     {
         eprintln!("Error: {e}");
+        let mut source = std::error::Error::source(&e);
+        while let Some(cause) = source {
+            eprintln!("  Caused by: {cause}");
+            source = cause.source();
+        }
     }
 }
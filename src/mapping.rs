@@ -0,0 +1,155 @@
+use crate::error::ClimarkError;
+use crowdmark::PageMap;
+use std::io::{self, BufRead as _, Write as _};
+
+/// Where a question's answer begins within the PDF.
+pub struct Start {
+    /// Zero-based index of the page the question starts on.
+    pub page: usize,
+    /// The question that starts here, or `None` for a section that belongs
+    /// to no question, such as an appendix.
+    pub question: Option<usize>,
+    /// Whether the previous question's answer continues onto the top of
+    /// this page.
+    pub shares_page: bool,
+}
+
+/// Assigns each page to the question that starts on it or most recently
+/// before it. `starts` must be in document order. Pages that belong to no
+/// question are reported and left out.
+pub fn assemble(starts: &[Start], page_count: usize) -> Option<PageMap> {
+    let mut map = PageMap::new();
+    let mut unmatched = Vec::new();
+    let mut current = None;
+    let mut starts = starts.iter().peekable();
+    for page in 0..page_count {
+        let mut claimed = false;
+        let mut first = true;
+        while let Some(start) = starts.next_if(|start| start.page <= page) {
+            if let Some(question) = current
+                && (!first || start.shares_page)
+            {
+                map.insert(question, [page + 1]);
+                claimed = true;
+            }
+            current = start.question;
+            first = false;
+        }
+
+        if let Some(question) = current {
+            map.insert(question, [page + 1]);
+        } else if !claimed {
+            unmatched.push(page + 1);
+        }
+    }
+
+    // Questions that start on the same page as the next one would claim it
+    // twice.
+    let mut deduplicated = PageMap::new();
+    for (question, pages) in map.iter() {
+        let mut pages = pages.to_vec();
+        pages.dedup();
+        deduplicated.insert(question, pages);
+    }
+
+    if !unmatched.is_empty() {
+        let pages: Vec<_> = unmatched.iter().map(ToString::to_string).collect();
+        eprintln!(
+            "Warning: Not uploading page(s) {}, which do not belong to any question",
+            pages.join(", ")
+        );
+    }
+    (!deduplicated.is_empty()).then_some(deduplicated)
+}
+
+/// Extracts the question number from headings such as "Question 3",
+/// "Q3: Limits", "q.3" or "Problem 2(b)".
+pub fn question_number(heading: &str) -> Option<usize> {
+    let heading = heading.trim().to_lowercase();
+    let rest = heading
+        .strip_prefix("question")
+        .or_else(|| heading.strip_prefix("problem"))
+        .or_else(|| heading.strip_prefix('q'))?
+        .trim_start_matches(|c: char| c.is_whitespace() || matches!(c, '.' | '#'));
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok().filter(|&n| n > 0)
+}
+
+/// Shows an inferred page map and asks the user to accept it. The PDF is
+/// read from stdin, so the answer is read from the terminal instead.
+pub fn confirm(map: &PageMap) -> Result<(), ClimarkError> {
+    eprintln!("Inferred page map (reusable with --map): {map}");
+    for (question, pages) in map.iter() {
+        let pages: Vec<_> = pages.iter().map(ToString::to_string).collect();
+        eprintln!("  Question {question}: page(s) {}", pages.join(", "));
+    }
+    eprint!("Upload with this mapping? [y/N] ");
+    io::stderr().flush()?;
+
+    #[cfg(windows)]
+    const TERMINAL: &str = "CONIN$";
+    #[cfg(not(windows))]
+    const TERMINAL: &str = "/dev/tty";
+    let terminal = std::fs::File::open(TERMINAL).map_err(|_e| ClimarkError::NoTerminal)?;
+    let mut answer = String::new();
+    io::BufReader::new(terminal).read_line(&mut answer)?;
+
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        Err(ClimarkError::Cancelled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Start, assemble, question_number};
+
+    fn start(page: usize, question: Option<usize>, shares_page: bool) -> Start {
+        Start {
+            page,
+            question,
+            shares_page,
+        }
+    }
+
+    #[test]
+    fn question_numbers_are_read_from_common_heading_styles() {
+        assert_eq!(question_number("Question 3"), Some(3));
+        assert_eq!(question_number("  q.12: Limits"), Some(12));
+        assert_eq!(question_number("Q#4"), Some(4));
+        assert_eq!(question_number("Problem 2(b)"), Some(2));
+        assert_eq!(question_number("Question 0"), None);
+        assert_eq!(question_number("Quadratics"), None);
+        assert_eq!(question_number("Answer to question 3"), None);
+    }
+
+    #[test]
+    fn pages_go_to_the_most_recent_question() {
+        let starts = [
+            start(0, Some(1), false),
+            start(2, Some(2), true),
+            start(3, Some(3), false),
+        ];
+
+        let map = assemble(&starts, 5).expect("Expected a page map");
+
+        assert_eq!(map.to_string(), "1=1-3,2=3,3=4-5");
+    }
+
+    #[test]
+    fn pages_outside_questions_are_left_out() {
+        let starts = [
+            start(1, Some(1), false),
+            start(2, Some(2), false),
+            start(3, None, false),
+        ];
+
+        let map = assemble(&starts, 5).expect("Expected a page map");
+
+        assert_eq!(map.to_string(), "1=2,2=3");
+        assert!(assemble(&[start(0, None, false)], 2).is_none());
+    }
+}
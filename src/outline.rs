@@ -0,0 +1,225 @@
+use crate::error::ClimarkError;
+use crate::mapping::{self, Start};
+use crowdmark::PageMap;
+use hayro::hayro_syntax::Pdf;
+use hayro::hayro_syntax::object::dict::keys::{
+    A, D, DEST, DESTS, FIRST, KIDS, NAMES, NEXT, OUTLINES, TITLE,
+};
+use hayro::hayro_syntax::object::{Array, Dict, MaybeRef, Object, ObjectIdentifier};
+use std::collections::HashMap;
+
+/// Guards against cyclic `/First` and `/Next` links in malformed outlines.
+const MAX_OUTLINE_ENTRIES: usize = 10_000;
+
+/// An outline entry along with the zero-based index of the page it points
+/// to.
+struct Entry {
+    depth: usize,
+    page: Option<usize>,
+    title: String,
+}
+
+/// Builds a page map from the PDF's outline. Each bookmark titled like
+/// "Question 3" or "Q3" starts question 3, which runs until the next
+/// bookmark outside that question or the end of the document.
+pub fn page_map(pdf: &Pdf) -> Result<PageMap, ClimarkError> {
+    let root = pdf
+        .xref()
+        .get::<Dict<'_>>(pdf.xref().root_id())
+        .ok_or(ClimarkError::PdfParse)?;
+    let page_indices: HashMap<ObjectIdentifier, usize> = pdf
+        .pages()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, page)| page.raw().obj_id().map(|id| (id, index)))
+        .collect();
+
+    let mut entries = Vec::new();
+    if let Some(outlines) = root.get::<Dict<'_>>(OUTLINES) {
+        collect_entries(&root, &page_indices, &outlines, 0, &mut entries);
+    }
+
+    mapping::assemble(&starts(&entries), pdf.pages().len()).ok_or(ClimarkError::NoOutlineQuestions)
+}
+
+/// Finds where questions start from the outline entries, in document
+/// order. Each question bookmark opens a section that ends at the next
+/// bookmark not nested inside it.
+fn starts(entries: &[Entry]) -> Vec<Start> {
+    let mut starts = Vec::new();
+    let mut question_depth = None;
+    for entry in entries {
+        if question_depth.is_some_and(|depth| entry.depth > depth) {
+            continue;
+        }
+        let question = mapping::question_number(&entry.title);
+        question_depth = question.map(|_| entry.depth);
+        match entry.page {
+            Some(page) => starts.push(Start {
+                page,
+                question,
+                shares_page: false,
+            }),
+            None if question.is_some() => {
+                eprintln!(
+                    "Warning: Ignoring bookmark {:?}, which does not point to a page",
+                    entry.title
+                );
+            }
+            None => {}
+        }
+    }
+    starts.sort_by_key(|start| start.page);
+    starts
+}
+
+fn collect_entries(
+    root: &Dict<'_>,
+    page_indices: &HashMap<ObjectIdentifier, usize>,
+    parent: &Dict<'_>,
+    depth: usize,
+    entries: &mut Vec<Entry>,
+) {
+    let mut item = parent.get::<Dict<'_>>(FIRST);
+    while let Some(dict) = item {
+        if entries.len() >= MAX_OUTLINE_ENTRIES {
+            return;
+        }
+        entries.push(Entry {
+            depth,
+            page: destination(&dict)
+                .and_then(|dest| resolve_destination(root, dest))
+                .and_then(|dest| page_index(&dest, page_indices)),
+            title: dict
+                .get::<hayro::hayro_syntax::object::String<'_>>(TITLE)
+                .map(|title| decode_text(&title))
+                .unwrap_or_default(),
+        });
+        collect_entries(root, page_indices, &dict, depth + 1, entries);
+        item = dict.get::<Dict<'_>>(NEXT);
+    }
+}
+
+/// Returns an outline item's destination, either given directly or through
+/// a go-to action.
+fn destination<'a>(item: &Dict<'a>) -> Option<Object<'a>> {
+    item.get::<Object<'_>>(DEST).or_else(|| {
+        item.get::<Dict<'_>>(A)
+            .and_then(|action| action.get::<Object<'_>>(D))
+    })
+}
+
+/// Resolves named destinations, as emitted by hyperref, to explicit ones.
+fn resolve_destination<'a>(root: &Dict<'a>, dest: Object<'a>) -> Option<Array<'a>> {
+    let target = match dest {
+        Object::Array(array) => return Some(array),
+        Object::Name(name) => {
+            let dests = root.get::<Dict<'_>>(DESTS)?;
+            dests.get::<Object<'_>>(name.as_ref())?
+        }
+        Object::String(name) => {
+            let tree = root.get::<Dict<'_>>(NAMES)?.get::<Dict<'_>>(DESTS)?;
+            lookup_name_tree(&tree, name.as_bytes(), 0)?
+        }
+        _ => return None,
+    };
+
+    match target {
+        Object::Array(array) => Some(array),
+        Object::Dict(dict) => dict.get::<Array<'_>>(D),
+        _ => None,
+    }
+}
+
+fn lookup_name_tree<'a>(node: &Dict<'a>, key: &[u8], depth: usize) -> Option<Object<'a>> {
+    if depth > 32 {
+        return None;
+    }
+
+    if let Some(names) = node.get::<Array<'_>>(NAMES) {
+        let mut iter = names.flex_iter();
+        while let Some(name) = iter.next::<hayro::hayro_syntax::object::String<'_>>() {
+            let value = iter.next::<Object<'_>>()?;
+            if name.as_bytes() == key {
+                return Some(value);
+            }
+        }
+    }
+
+    node.get::<Array<'_>>(KIDS)?
+        .iter::<Dict<'_>>()
+        .find_map(|kid| lookup_name_tree(&kid, key, depth + 1))
+}
+
+fn page_index(dest: &Array<'_>, page_indices: &HashMap<ObjectIdentifier, usize>) -> Option<usize> {
+    match dest.raw_iter().next()? {
+        MaybeRef::Ref(page) => page_indices.get(&page.into()).copied(),
+        // Page numbers are only valid in remote destinations, but some
+        // producers use them anyway.
+        MaybeRef::NotRef(Object::Number(number)) => usize::try_from(number.as_i64()).ok(),
+        MaybeRef::NotRef(_) => None,
+    }
+}
+
+/// Decodes a PDF text string, which is either UTF-16BE with a byte order
+/// mark or (approximately) Latin-1.
+fn decode_text(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<_> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, decode_text, starts};
+
+    fn entry(depth: usize, page: Option<usize>, title: &str) -> Entry {
+        Entry {
+            depth,
+            page,
+            title: title.to_owned(),
+        }
+    }
+
+    #[test]
+    fn nested_bookmarks_stay_in_their_question() {
+        let entries = [
+            entry(0, Some(0), "Cover"),
+            entry(0, Some(1), "Question 1"),
+            entry(1, Some(2), "Part (b)"),
+            entry(0, Some(4), "Q2"),
+            entry(0, Some(3), "Question 3"),
+            entry(0, None, "Question 4"),
+            entry(0, Some(6), "Appendix"),
+        ];
+
+        let starts: Vec<_> = starts(&entries)
+            .iter()
+            .map(|start| (start.page, start.question))
+            .collect();
+
+        assert_eq!(
+            starts,
+            [
+                (0, None),
+                (1, Some(1)),
+                (3, Some(3)),
+                (4, Some(2)),
+                (6, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn titles_decode_from_utf16_and_latin1() {
+        assert_eq!(decode_text(&[0xFE, 0xFF, 0x00, 0x51, 0x00, 0x31]), "Q1");
+        assert_eq!(decode_text(b"Probl\xe8me 2"), "Probl\u{e8}me 2");
+    }
+}
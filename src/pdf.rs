@@ -0,0 +1,200 @@
+use crate::error::ClimarkError;
+use pdf_writer::{Content, Filter, Finish as _, Name, Pdf, Rect, Ref, Str};
+
+/// Width of every page, in points (A4).
+const PAGE_WIDTH: f32 = 595.0;
+const MARGIN: f32 = 36.0;
+const HEADING_SIZE: f32 = 14.0;
+const TEXT_SIZE: f32 = 10.0;
+const LEADING: f32 = 1.4;
+/// Rough number of Helvetica characters that fit between the margins.
+const WRAP_COLUMNS: usize = 95;
+
+/// One page of the generated PDF: an optional block of text above an
+/// optional JPEG image scaled to the page width.
+#[derive(Default)]
+pub struct Page {
+    pub heading: Option<String>,
+    pub image: Option<Vec<u8>>,
+    pub lines: Vec<String>,
+}
+
+struct JpegInfo {
+    color_components: u8,
+    height: u16,
+    width: u16,
+}
+
+/// Renders `pages` into a PDF document.
+pub fn render(pages: &[Page]) -> Result<Vec<u8>, ClimarkError> {
+    let mut pdf = Pdf::new();
+    let mut next_ref = Ref::new(1);
+    let catalog_id = next_ref.bump();
+    let tree_id = next_ref.bump();
+    let regular_id = next_ref.bump();
+    let bold_id = next_ref.bump();
+    let page_ids: Vec<_> = pages.iter().map(|_| next_ref.bump()).collect();
+
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(i32::try_from(page_ids.len()).unwrap_or(i32::MAX));
+    pdf.type1_font(regular_id)
+        .base_font(Name(b"Helvetica"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+    pdf.type1_font(bold_id)
+        .base_font(Name(b"Helvetica-Bold"))
+        .encoding_predefined(Name(b"WinAnsiEncoding"));
+
+    for (page, page_id) in pages.iter().zip(page_ids) {
+        let lines: Vec<_> = page.lines.iter().flat_map(|line| wrap(line)).collect();
+        let has_text = page.heading.is_some() || !lines.is_empty();
+        let text_height = if has_text {
+            let heading_height = page
+                .heading
+                .as_ref()
+                .map_or(0.0, |_| HEADING_SIZE * LEADING);
+            let body_height = lines.len() as f32 * TEXT_SIZE * LEADING;
+            heading_height + body_height + 2.0 * MARGIN
+        } else {
+            0.0
+        };
+
+        let image = page
+            .image
+            .as_ref()
+            .map(|data| jpeg_info(data).map(|info| (data, info)))
+            .transpose()?;
+        let image_height = image.as_ref().map_or(0.0, |(_, info)| {
+            f32::from(info.height) * PAGE_WIDTH / f32::from(info.width)
+        });
+        let page_height = (text_height + image_height).max(2.0 * MARGIN);
+
+        let mut content = Content::new();
+        if has_text {
+            let mut y = page_height - MARGIN;
+            content.begin_text();
+            if let Some(heading) = &page.heading {
+                y -= HEADING_SIZE;
+                content.set_font(Name(b"F2"), HEADING_SIZE);
+                content.set_text_matrix([1.0, 0.0, 0.0, 1.0, MARGIN, y]);
+                content.show(Str(&win_ansi(heading)));
+                y -= HEADING_SIZE * (LEADING - 1.0);
+            }
+            content.set_font(Name(b"F1"), TEXT_SIZE);
+            for line in &lines {
+                y -= TEXT_SIZE;
+                content.set_text_matrix([1.0, 0.0, 0.0, 1.0, MARGIN, y]);
+                content.show(Str(&win_ansi(line)));
+                y -= TEXT_SIZE * (LEADING - 1.0);
+            }
+            content.end_text();
+        }
+        if image.is_some() {
+            content.save_state();
+            content.transform([PAGE_WIDTH, 0.0, 0.0, image_height, 0.0, 0.0]);
+            content.x_object(Name(b"Im1"));
+            content.restore_state();
+        }
+
+        let content_id = next_ref.bump();
+        let mut pdf_page = pdf.page(page_id);
+        pdf_page
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, page_height))
+            .parent(tree_id)
+            .contents(content_id);
+        let mut resources = pdf_page.resources();
+        resources
+            .fonts()
+            .pair(Name(b"F1"), regular_id)
+            .pair(Name(b"F2"), bold_id);
+        let image_id = image.as_ref().map(|_| next_ref.bump());
+        if let Some(image_id) = image_id {
+            resources.x_objects().pair(Name(b"Im1"), image_id);
+        }
+        resources.finish();
+        pdf_page.finish();
+
+        if let (Some((data, info)), Some(image_id)) = (image, image_id) {
+            let mut xobject = pdf.image_xobject(image_id, data);
+            xobject.filter(Filter::DctDecode);
+            xobject
+                .width(i32::from(info.width))
+                .height(i32::from(info.height))
+                .bits_per_component(8);
+            match info.color_components {
+                1 => xobject.color_space().device_gray(),
+                4 => xobject.color_space().device_cmyk(),
+                _ => xobject.color_space().device_rgb(),
+            };
+        }
+
+        pdf.stream(content_id, &content.finish());
+    }
+
+    Ok(pdf.finish())
+}
+
+/// Reads the dimensions and colour components from a JPEG's start-of-frame
+/// segment.
+fn jpeg_info(data: &[u8]) -> Result<JpegInfo, ClimarkError> {
+    if data.get(..2) != Some(&[0xFF, 0xD8]) {
+        return Err(ClimarkError::JpegParse);
+    }
+
+    let mut offset = 2;
+    while let Some(&[0xFF, marker, len_hi, len_lo]) = data.get(offset..offset + 4) {
+        let length = usize::from(u16::from_be_bytes([len_hi, len_lo]));
+        let is_start_of_frame =
+            matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_start_of_frame {
+            let Some(&[_precision, h_hi, h_lo, w_hi, w_lo, color_components]) =
+                data.get(offset + 4..offset + 10)
+            else {
+                break;
+            };
+            let info = JpegInfo {
+                color_components,
+                height: u16::from_be_bytes([h_hi, h_lo]),
+                width: u16::from_be_bytes([w_hi, w_lo]),
+            };
+            if info.width == 0 || info.height == 0 {
+                break;
+            }
+            return Ok(info);
+        }
+        offset += 2 + length;
+    }
+
+    Err(ClimarkError::JpegParse)
+}
+
+/// Greedily wraps `text` on whitespace so each line fits the page width.
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > WRAP_COLUMNS {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Encodes `text` for the base-14 fonts, replacing characters outside
+/// Latin-1 with `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{A0}'..='\u{FF}' => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
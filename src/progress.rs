@@ -0,0 +1,151 @@
+use crate::cli::OutputFormat;
+use crowdmark::{PageMap, UploadEvent};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, IsTerminal as _};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Shows upload events as they happen: as NDJSON on stdout for
+/// `--format json`, as progress bars when stderr is a terminal, and not at
+/// all otherwise.
+pub struct Progress {
+    pub sender: Option<UnboundedSender<UploadEvent>>,
+    display: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    pub fn start(format: &OutputFormat, map: &PageMap) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<UploadEvent>();
+        let display = match *format {
+            OutputFormat::Json => tokio::spawn(async move {
+                while let Some(event) = receiver.recv().await {
+                    if let Ok(line) = serde_json::to_string(&event) {
+                        println!("{line}");
+                    }
+                }
+            }),
+            OutputFormat::Plain | OutputFormat::Pretty if io::stderr().is_terminal() => {
+                let mut bars = Bars::new(map);
+                tokio::spawn(async move {
+                    while let Some(event) = receiver.recv().await {
+                        bars.update(event);
+                    }
+                    bars.overall.finish();
+                })
+            }
+            OutputFormat::Plain | OutputFormat::Pretty => {
+                return Self {
+                    sender: None,
+                    display: None,
+                };
+            }
+        };
+        Self {
+            sender: Some(sender),
+            display: Some(display),
+        }
+    }
+
+    pub fn report(&self, event: UploadEvent) {
+        report(self.sender.as_ref(), event);
+    }
+
+    /// Waits for every event sent so far to be shown. Any other senders must
+    /// have been dropped already.
+    pub async fn finish(self) {
+        drop(self.sender);
+        if let Some(display) = self.display {
+            // A broken display does not affect the upload itself.
+            display.await.ok();
+        }
+    }
+}
+
+/// Sends `event` if progress is being shown.
+pub fn report(sender: Option<&UnboundedSender<UploadEvent>>, event: UploadEvent) {
+    if let Some(sender) = sender {
+        // The display only stops listening once every sender is gone.
+        sender.send(event).ok();
+    }
+}
+
+/// A bar for each page in flight, above one for the upload as a whole.
+struct Bars {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    pages: HashMap<usize, ProgressBar>,
+    /// Pages the page map uploads; the rest are only rendered.
+    uploaded: BTreeSet<usize>,
+}
+
+impl Bars {
+    fn new(map: &PageMap) -> Self {
+        let multi = MultiProgress::new();
+        let uploads = map.iter().map(|(_, pages)| pages.len()).sum::<usize>();
+        let overall = multi.add(ProgressBar::new(uploads as u64));
+        overall.set_style(
+            ProgressStyle::with_template("{prefix:>8} [{bar:30}] {pos}/{len} pages {msg}")
+                .expect("Invalid progress template")
+                .progress_chars("=> "),
+        );
+        overall.set_prefix("Total");
+        Self {
+            multi,
+            overall,
+            pages: HashMap::new(),
+            uploaded: map
+                .iter()
+                .flat_map(|(_, pages)| pages.iter().copied())
+                .collect(),
+        }
+    }
+
+    fn update(&mut self, event: UploadEvent) {
+        match event {
+            UploadEvent::RenderStarted { page } => self.page(page).set_message("rendering"),
+            UploadEvent::RenderFinished { page } => {
+                if self.uploaded.contains(&page) {
+                    self.page(page).set_message("waiting to upload");
+                } else {
+                    self.remove(page);
+                }
+            }
+            UploadEvent::UploadStarted { page, total } => {
+                let bar = self.page(page);
+                bar.set_length(total);
+                bar.set_position(0);
+                bar.set_message("uploading");
+            }
+            UploadEvent::UploadProgress { page, sent, .. } => self.page(page).set_position(sent),
+            UploadEvent::UploadFinished { page } => self.page(page).set_message("registering"),
+            UploadEvent::PageRegistered { page, .. } => {
+                self.remove(page);
+                self.overall.inc(1);
+            }
+            UploadEvent::Submitted => self.overall.set_message("submitted"),
+            _ => {}
+        }
+    }
+
+    /// The bar for `page`, added above the overall one if it is not shown yet.
+    fn page(&mut self, page: usize) -> &ProgressBar {
+        self.pages.entry(page).or_insert_with(|| {
+            let bar = self.multi.insert_before(&self.overall, ProgressBar::new(0));
+            bar.set_style(
+                ProgressStyle::with_template("{prefix:>8} [{bar:30}] {bytes}/{total_bytes} {msg}")
+                    .expect("Invalid progress template")
+                    .progress_chars("=> "),
+            );
+            bar.set_prefix(format!("Page {page}"));
+            bar
+        })
+    }
+
+    fn remove(&mut self, page: usize) {
+        if let Some(bar) = self.pages.remove(&page) {
+            bar.finish_and_clear();
+            self.multi.remove(&bar);
+        }
+    }
+}
@@ -1,38 +1,101 @@
+use crate::cli::OutputFormat;
 use crate::error::ClimarkError;
-use crowdmark::Client;
+use crate::progress::{self, Progress};
+use crate::{headings, mapping, outline};
+use crowdmark::error::CrowdmarkError;
+use crowdmark::{AssessmentId, Client, PageMap, UploadEvent, UploadOptions};
 use hayro::hayro_interpret::InterpreterSettings;
 use hayro::hayro_syntax::Pdf;
 use hayro::vello_cpu::color::palette::css::WHITE;
 use hayro::{RenderCache, RenderSettings, render};
 use jpeg_encoder::{ColorType, Encoder};
 use std::io::{self, Read as _};
+use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::sync::mpsc;
 
+/// How PDF pages are assigned to questions.
+pub enum MapSource<'a> {
+    /// Page `n` answers question `n`.
+    OnePerQuestion,
+    /// A page map in the syntax [`PageMap`] parses.
+    Explicit(&'a str),
+    /// Inferred from the PDF's bookmarks.
+    Outline,
+    /// Inferred from headings in the PDF's text.
+    Text,
+}
+
+#[expect(clippy::too_many_arguments)]
 pub async fn upload_assessment(
     client: Client,
     assessment_id: &str,
+    format: &OutputFormat,
+    map_source: MapSource<'_>,
     scale: f32,
+    only_questions: &[usize],
+    mut options: UploadOptions,
     nosubmit: bool,
+    yes: bool,
 ) -> Result<(), ClimarkError> {
+    let assessment_id: AssessmentId = assessment_id.parse()?;
+    let map = match map_source {
+        MapSource::Explicit(map) => Some(check_selected(map.parse()?, only_questions)?),
+        _ => None,
+    };
     let mut buffer = Vec::new();
     io::stdin()
         .read_to_end(&mut buffer)
         .map_err(|_e| ClimarkError::StdinRead)?;
     let data = Arc::new(buffer);
     let pdf = Pdf::new(data).map_err(|_e| ClimarkError::PdfParse)?;
-    let interpreter_settings = InterpreterSettings::default();
-    let render_settings = RenderSettings {
-        x_scale: scale,
-        y_scale: scale,
-        bg_color: WHITE,
-        ..Default::default()
+    let inferred = match map_source {
+        MapSource::Outline => Some(check_selected(outline::page_map(&pdf)?, only_questions)?),
+        MapSource::Text => Some(check_selected(headings::page_map(&pdf)?, only_questions)?),
+        MapSource::OnePerQuestion | MapSource::Explicit(_) => None,
+    };
+    if let Some(inferred) = &inferred
+        && !yes
+    {
+        mapping::confirm(inferred)?;
+    }
+    let map = inferred.or(map);
+    let page_count = pdf.pages().len();
+    let map = match (map, only_questions) {
+        (Some(map), _) => map,
+        (None, []) => PageMap::one_per_question(page_count),
+        (None, &[question]) => {
+            let mut map = PageMap::new();
+            map.insert(question, 1..=page_count);
+            map
+        }
+        (None, _) => return Err(ClimarkError::AmbiguousQuestions),
     };
-    let cache = RenderCache::new();
+    let progress = Progress::start(format, &map);
+    if let Some(sender) = &progress.sender {
+        options = options.progress(sender.clone());
+    }
 
-    let pages = pdf
-        .pages()
-        .iter()
-        .map(|page| {
+    // Rendering is CPU-bound, so it runs on the blocking pool and hands each
+    // page over as soon as it is encoded. The channel keeps it from running
+    // more than a page ahead of the uploads.
+    let (sender, receiver) = mpsc::channel(1);
+    let reporter = progress.sender.clone();
+    let renderer = tokio::task::spawn_blocking(move || {
+        let interpreter_settings = InterpreterSettings::default();
+        let render_settings = RenderSettings {
+            x_scale: scale,
+            y_scale: scale,
+            bg_color: WHITE,
+            ..Default::default()
+        };
+        let cache = RenderCache::new();
+
+        for (index, page) in pdf.pages().iter().enumerate() {
+            progress::report(
+                reporter.as_ref(),
+                UploadEvent::RenderStarted { page: index + 1 },
+            );
             let pixmap = render(page, &cache, &interpreter_settings, &render_settings);
             let width = pixmap.width();
             let height = pixmap.height();
@@ -46,16 +109,66 @@ pub async fn upload_assessment(
             encoder
                 .encode(&rgb, width, height, ColorType::Rgb)
                 .expect("Failed to encode JPEG");
-            jpeg_data
-        })
-        .enumerate();
-
-    let csrf = client.get_csrf().await?;
-    client
-        .upload_assessment(&csrf, assessment_id, pages)
-        .await?;
-    if !nosubmit {
-        client.submit_assessment(&csrf, assessment_id).await?;
+            progress::report(
+                reporter.as_ref(),
+                UploadEvent::RenderFinished { page: index + 1 },
+            );
+            // The upload stops receiving pages when it fails.
+            if sender.blocking_send(jpeg_data).is_err() {
+                break;
+            }
+        }
+    });
+    if let Some(journal) = journal_path(&assessment_id) {
+        options = options.journal(journal);
+    }
+    let mut result = client
+        .upload_streamed_assessment(&assessment_id, &map, page_count, receiver, &options)
+        .await;
+    drop(options);
+    let rendered = renderer.await;
+    if result.is_ok() && !nosubmit {
+        result = client.submit_assessment(&assessment_id).await;
+        if result.is_ok() {
+            progress.report(UploadEvent::Submitted);
+        }
     }
-    Ok(())
+    progress.finish().await;
+    rendered.map_err(CrowdmarkError::from)?;
+    Ok(result?)
+}
+
+/// Returns `map` unless `only_questions` is non-empty and `map` gives pages
+/// to questions not among them, which would replace those questions' pages
+/// without the user asking to.
+fn check_selected(map: PageMap, only_questions: &[usize]) -> Result<PageMap, ClimarkError> {
+    if only_questions.is_empty() {
+        return Ok(map);
+    }
+    let unselected: Vec<_> = map
+        .iter()
+        .map(|(question, _)| question)
+        .filter(|question| !only_questions.contains(question))
+        .map(|question| question.to_string())
+        .collect();
+    if unselected.is_empty() {
+        Ok(map)
+    } else {
+        Err(ClimarkError::UnselectedQuestions(unselected.join(", ")))
+    }
+}
+
+/// Where the journal of an interrupted upload to `assessment_id` is kept, so
+/// that running the same upload again only sends the pages that are missing.
+fn journal_path(assessment_id: &AssessmentId) -> Option<PathBuf> {
+    #[cfg(windows)]
+    let cache = std::env::var_os("LOCALAPPDATA").map(PathBuf::from);
+    #[cfg(not(windows))]
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+
+    let dir = cache?.join("climark").join("uploads");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join(format!("{assessment_id}.json")))
 }